@@ -0,0 +1,48 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+// Benchmarks of string escaping, the bulk of encoding text-heavy values
+
+#![allow(unstable)]
+
+extern crate test;
+extern crate xmlrpc;
+
+use std::default::Default;
+use std::iter::repeat;
+use test::Bencher;
+use xmlrpc::{encode_with, EncoderOptions};
+
+fn bench_escape(b: &mut Bencher, chunk: &str, options: EncoderOptions) {
+    let s: String = repeat(chunk).take(64 * 1024 / chunk.len()).collect();
+    b.bytes = s.len() as u64;
+    b.iter(|| encode_with(&s, options));
+}
+
+#[bench]
+fn bench_escape_plain(b: &mut Bencher) {
+    bench_escape(b, "the quick brown fox jumps over the lazy dog ", Default::default());
+}
+
+#[bench]
+fn bench_escape_markup(b: &mut Bencher) {
+    bench_escape(b, "<node name=\"a & b\">'x'</node> ", Default::default());
+}
+
+#[bench]
+fn bench_escape_non_ascii(b: &mut Bencher) {
+    bench_escape(b, "caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e ", Default::default());
+}
+
+#[bench]
+fn bench_escape_non_ascii_to_ascii(b: &mut Bencher) {
+    let options = EncoderOptions { ascii_only: true, ..Default::default() };
+    bench_escape(b, "caf\u{e9} cr\u{e8}me br\u{fb}l\u{e9}e ", options);
+}
@@ -0,0 +1,204 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::string;
+use std::time::Duration;
+
+use time;
+
+use client::Client;
+use protocol::{Request, Response};
+
+/// The state of a circuit breaker.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum BreakerState {
+    /// Calls pass through and failures are counted
+    Closed,
+    /// Calls fail immediately until the cool-down has elapsed
+    Open,
+    /// A single trial call is let through to probe the endpoint
+    HalfOpen,
+}
+
+/// The errors that can arise from a call through a circuit breaker.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum BreakerError {
+    /// The breaker is open and the call was not attempted
+    Unavailable,
+    /// The call was attempted and failed
+    CallFailed,
+}
+
+/// Stops calling an endpoint after repeated failures, failing fast until a
+/// cool-down period has passed.
+pub struct CircuitBreaker {
+    failure_threshold: usize,
+    cool_down: Duration,
+    state: Cell<BreakerState>,
+    failures: Cell<usize>,
+    opened_at: Cell<u64>,
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker which opens after `failure_threshold` consecutive
+    /// failures and stays open for `cool_down`.
+    pub fn new(failure_threshold: usize, cool_down: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: failure_threshold,
+            cool_down: cool_down,
+            state: Cell::new(BreakerState::Closed),
+            failures: Cell::new(0),
+            opened_at: Cell::new(0),
+        }
+    }
+
+    /// Returns the current state, moving from open to half-open once the
+    /// cool-down has elapsed.
+    pub fn state(&self) -> BreakerState {
+        if self.state.get() == BreakerState::Open {
+            let cool_down = self.cool_down.num_nanoseconds().unwrap_or(0) as u64;
+            if time::precise_time_ns() >= self.opened_at.get() + cool_down {
+                self.state.set(BreakerState::HalfOpen);
+            }
+        }
+        self.state.get()
+    }
+
+    /// Performs `request` through `client` unless the breaker is open.
+    pub fn call(&self, client: &Client, request: &Request) -> Result<Response, BreakerError> {
+        if self.state() == BreakerState::Open {
+            return Err(BreakerError::Unavailable);
+        }
+        match client.remote_call(request) {
+            Some(response) => {
+                self.record_success();
+                Ok(response)
+            }
+            None => {
+                self.record_failure();
+                Err(BreakerError::CallFailed)
+            }
+        }
+    }
+
+    /// Records a successful call, closing the breaker.
+    pub fn record_success(&self) {
+        self.failures.set(0);
+        self.state.set(BreakerState::Closed);
+    }
+
+    /// Records a failed call, opening the breaker if the threshold is reached
+    /// or the half-open trial call failed.
+    pub fn record_failure(&self) {
+        let failures = self.failures.get() + 1;
+        self.failures.set(failures);
+        if self.state.get() == BreakerState::HalfOpen || failures >= self.failure_threshold {
+            self.state.set(BreakerState::Open);
+            self.opened_at.set(time::precise_time_ns());
+        }
+    }
+}
+
+/// A separate circuit breaker for each method called, so that one failing
+/// method does not cut off the rest of an endpoint.
+pub struct MethodBreakers {
+    failure_threshold: usize,
+    cool_down: Duration,
+    breakers: RefCell<HashMap<string::String, CircuitBreaker>>,
+}
+
+impl MethodBreakers {
+    /// Creates per-method breakers sharing the given settings.
+    pub fn new(failure_threshold: usize, cool_down: Duration) -> MethodBreakers {
+        MethodBreakers {
+            failure_threshold: failure_threshold,
+            cool_down: cool_down,
+            breakers: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the state of the breaker for `method`.
+    pub fn state(&self, method: &str) -> BreakerState {
+        match self.breakers.borrow().get(method) {
+            Some(breaker) => breaker.state(),
+            None => BreakerState::Closed,
+        }
+    }
+
+    /// Performs `request` through `client` unless its method's breaker is open.
+    pub fn call(&self, client: &Client, request: &Request) -> Result<Response, BreakerError> {
+        let mut breakers = self.breakers.borrow_mut();
+        if !breakers.contains_key(&request.method) {
+            let breaker = CircuitBreaker::new(self.failure_threshold, self.cool_down);
+            breakers.insert(request.method.clone(), breaker);
+        }
+        breakers.get(&request.method).unwrap().call(client, request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreakerError, BreakerState, CircuitBreaker, MethodBreakers};
+    use client::Client;
+    use protocol::Request;
+    use std::time::Duration;
+
+    // nothing listens on port 1
+    const UNREACHABLE: &'static str = "http://127.0.0.1:1/RPC2";
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::hours(1));
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        // a success resets the count
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+
+    #[test]
+    fn test_half_open_trial() {
+        let breaker = CircuitBreaker::new(1, Duration::zero());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        // a failed trial opens the breaker again
+        breaker.record_failure();
+        assert_eq!(breaker.state.get(), BreakerState::Open);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn test_open_breaker_fails_fast() {
+        let breaker = CircuitBreaker::new(1, Duration::hours(1));
+        breaker.record_failure();
+        let client = Client::new(UNREACHABLE).unwrap();
+        let result = breaker.call(&client, &Request::new("ping").finalize());
+        assert_eq!(result.err(), Some(BreakerError::Unavailable));
+        assert_eq!(client.stats().calls, 0);
+    }
+
+    #[test]
+    fn test_method_breakers_are_separate() {
+        let breakers = MethodBreakers::new(1, Duration::hours(1));
+        let client = Client::new(UNREACHABLE).unwrap();
+        // an invalid method name fails without reaching the network
+        let failing = Request::new("bad name").finalize();
+        assert_eq!(breakers.call(&client, &failing).err(), Some(BreakerError::CallFailed));
+        assert_eq!(breakers.state("bad name"), BreakerState::Open);
+        assert_eq!(breakers.state("ping"), BreakerState::Closed);
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::collections::HashMap;
+use std::i64;
+use std::num::Int;
+use std::string;
+use std::time::Duration;
+
+use time;
+
+use protocol::{Request, Response, ResponseError};
+
+/// A bounded cache of responses keyed by method name and encoded
+/// parameters. Entries expire a fixed time after they are inserted.
+pub struct ResponseCache {
+    entries: HashMap<(string::String, string::String), (u64, Response)>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    /// Creates a cache holding at most `capacity` responses for `ttl` each.
+    pub fn new(capacity: usize, ttl: Duration) -> ResponseCache {
+        ResponseCache { entries: HashMap::new(), capacity: capacity, ttl: ttl }
+    }
+
+    /// Returns the cached response to `request`, if still fresh.
+    pub fn get(&mut self, request: &Request) -> Option<Response> {
+        let key = cache_key(request);
+        let expired = match self.entries.get(&key) {
+            Some(&(expires, ref response)) => {
+                if time::precise_time_ns() < expires {
+                    return Some(response.clone());
+                }
+                true
+            }
+            None => false,
+        };
+        if expired {
+            self.entries.remove(&key);
+        }
+        None
+    }
+
+    /// Stores the response to `request`, evicting the oldest entry if the
+    /// cache is full. Only successful responses are cached: never faults,
+    /// nor bodies that fail to parse.
+    pub fn insert(&mut self, request: &Request, response: &Response) {
+        let success = match response.value(0) {
+            Ok(_) | Err(ResponseError::MissingParam(_)) => true,
+            Err(_) => false,
+        };
+        if self.capacity == 0 || !success {
+            return;
+        }
+        let key = cache_key(request);
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        // a ttl too long to count in nanoseconds is as good as forever
+        let ttl = match self.ttl.num_nanoseconds().unwrap_or(i64::MAX) {
+            ns if ns < 0 => 0,
+            ns => ns as u64,
+        };
+        let expires = time::precise_time_ns().saturating_add(ttl);
+        self.entries.insert(key, (expires, response.clone()));
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the number of cached responses, including expired ones not
+    /// yet removed.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn evict_oldest(&mut self) {
+        // every entry lives for the same ttl, so the first to expire is the oldest
+        let mut oldest = None;
+        for (key, &(expires, _)) in self.entries.iter() {
+            match oldest {
+                Some((_, t)) if t <= expires => {}
+                _ => oldest = Some((key.clone(), expires)),
+            }
+        }
+        if let Some((key, _)) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+fn cache_key(request: &Request) -> (string::String, string::String) {
+    (request.method.clone(), request.document())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseCache;
+    use fixtures;
+    use protocol::{Request, Response};
+    use std::io::timer::sleep;
+    use std::time::Duration;
+
+    fn request(n: i32) -> Request {
+        Request::new("lookup").argument(&n).finalize()
+    }
+
+    fn response(n: i32) -> Response {
+        Response::new(fixtures::response_ok(&n).as_slice())
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let mut cache = ResponseCache::new(4, Duration::zero());
+        cache.insert(&request(1), &response(1));
+        assert_eq!(cache.get(&request(1)), None);
+        assert_eq!(cache.len(), 0);
+
+        // a ttl beyond the nanosecond range does not disable caching
+        let mut cache = ResponseCache::new(4, Duration::max_value());
+        cache.insert(&request(1), &response(1));
+        assert_eq!(cache.get(&request(1)), Some(response(1)));
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let mut cache = ResponseCache::new(2, Duration::minutes(1));
+        for n in range(0, 3) {
+            cache.insert(&request(n), &response(n));
+            sleep(Duration::milliseconds(1));
+        }
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&request(0)), None);
+        assert_eq!(cache.get(&request(1)), Some(response(1)));
+        assert_eq!(cache.get(&request(2)), Some(response(2)));
+    }
+
+    #[test]
+    fn test_only_successes_are_cached() {
+        let mut cache = ResponseCache::new(4, Duration::minutes(1));
+        cache.insert(&request(1), &Response::new(fixtures::fault(1, "boom").as_slice()));
+        cache.insert(&request(2), &Response::new("<html>Bad Gateway</html>"));
+        assert_eq!(cache.len(), 0);
+        let empty = Response::new("<methodResponse><params></params></methodResponse>");
+        cache.insert(&request(3), &empty);
+        assert_eq!(cache.get(&request(3)), Some(empty));
+    }
+}
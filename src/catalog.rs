@@ -0,0 +1,163 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::collections::BTreeMap;
+use std::string;
+
+use encoding::Xml;
+use schema::{Signature, SchemaError};
+
+/// The description of one method: its signatures and help text.
+#[derive(Clone, PartialEq, Show)]
+pub struct MethodInfo {
+    pub name: string::String,
+    pub signatures: Vec<Signature>,
+    pub help: string::String,
+}
+
+/// The errors that can arise while checking a call against a catalog.
+#[derive(Clone, PartialEq, Show)]
+pub enum CatalogError {
+    /// The method is not in the catalog
+    UnknownMethod(string::String),
+    /// The parameters match none of the method's signatures; carries the
+    /// error from the first
+    Schema(SchemaError),
+}
+
+/// Describes the methods an endpoint offers, answering the introspection
+/// calls system.listMethods, system.methodSignature and system.methodHelp
+/// and checking calls against the declared signatures.
+#[derive(Clone, PartialEq, Show)]
+pub struct MethodCatalog {
+    methods: BTreeMap<string::String, MethodInfo>,
+}
+
+impl MethodCatalog {
+    pub fn new() -> MethodCatalog {
+        MethodCatalog { methods: BTreeMap::new() }
+    }
+
+    /// Adds `name` with one signature and its help text. Adding a method
+    /// again appends another signature, for overloaded methods, and
+    /// replaces the help text unless the new text is empty.
+    pub fn method(mut self, name: &str, signature: Signature, help: &str) -> MethodCatalog {
+        if !self.methods.contains_key(name) {
+            let info = MethodInfo {
+                name: name.to_string(),
+                signatures: Vec::new(),
+                help: string::String::new(),
+            };
+            self.methods.insert(name.to_string(), info);
+        }
+        let info = self.methods.get_mut(name).unwrap();
+        info.signatures.push(signature);
+        if !help.is_empty() {
+            info.help = help.to_string();
+        }
+        self
+    }
+
+    /// Returns the description of `name`, if the catalog has it.
+    pub fn get(&self, name: &str) -> Option<&MethodInfo> {
+        self.methods.get(name)
+    }
+
+    /// Returns every method, by name.
+    pub fn methods(&self) -> Vec<&MethodInfo> {
+        self.methods.values().collect()
+    }
+
+    /// Checks a call of `name` with `params` against the method's
+    /// signatures, succeeding if any matches.
+    pub fn validate(&self, name: &str, params: &[Xml]) -> Result<(), CatalogError> {
+        let info = match self.methods.get(name) {
+            Some(info) => info,
+            None => return Err(CatalogError::UnknownMethod(name.to_string())),
+        };
+        let mut first = None;
+        for signature in info.signatures.iter() {
+            match signature.validate(params) {
+                Ok(()) => return Ok(()),
+                Err(e) => if first.is_none() { first = Some(e) },
+            }
+        }
+        match first {
+            Some(e) => Err(CatalogError::Schema(e)),
+            None => Ok(()),
+        }
+    }
+
+    /// The result of system.listMethods: an array of method names.
+    pub fn list_methods(&self) -> Xml {
+        Xml::Array(self.methods.keys().map(|name| Xml::String(name.clone())).collect())
+    }
+
+    /// The result of system.methodSignature: an array of signatures, each
+    /// an array of type names with the return type first.
+    pub fn method_signature(&self, name: &str) -> Option<Xml> {
+        self.methods.get(name).map(|info| {
+            Xml::Array(info.signatures.iter().map(|signature| signature.to_xml()).collect())
+        })
+    }
+
+    /// The result of system.methodHelp: the method's help text.
+    pub fn method_help(&self, name: &str) -> Option<Xml> {
+        self.methods.get(name).map(|info| Xml::String(info.help.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CatalogError, MethodCatalog};
+    use encoding::Xml;
+    use schema::{SchemaError, Signature, Type};
+
+    fn strings(v: &[&str]) -> Xml {
+        Xml::Array(v.iter().map(|s| Xml::String(s.to_string())).collect())
+    }
+
+    fn catalog() -> MethodCatalog {
+        MethodCatalog::new()
+            .method("math.add", Signature::new().returns(Type::Int).param(Type::Int).param(Type::Int),
+                    "Adds two integers.")
+            .method("math.add", Signature::new().returns(Type::Double)
+                    .param(Type::Double).param(Type::Double), "")
+            .method("echo", Signature::new().param(Type::Any), "Returns its argument.")
+    }
+
+    #[test]
+    fn test_introspection() {
+        let catalog = catalog();
+        assert_eq!(catalog.list_methods(), strings(&["echo", "math.add"]));
+        assert_eq!(catalog.method_signature("math.add"),
+                   Some(Xml::Array(vec![strings(&["int", "int", "int"]),
+                                        strings(&["double", "double", "double"])])));
+        assert_eq!(catalog.method_signature("echo"),
+                   Some(Xml::Array(vec![strings(&["undef", "undef"])])));
+        // an empty help text does not replace an earlier one
+        assert_eq!(catalog.method_help("math.add"),
+                   Some(Xml::String("Adds two integers.".to_string())));
+        assert_eq!(catalog.method_help("missing"), None);
+    }
+
+    #[test]
+    fn test_validate_overloads() {
+        let catalog = catalog();
+        assert_eq!(catalog.validate("math.add", &[Xml::I32(1), Xml::I32(2)]), Ok(()));
+        assert_eq!(catalog.validate("math.add", &[Xml::F64(1.0), Xml::F64(2.0)]), Ok(()));
+        // the error is the first signature's
+        assert_eq!(catalog.validate("math.add", &[Xml::I32(1), Xml::F64(2.0)]),
+                   Err(CatalogError::Schema(SchemaError::Mismatch("params[1]".to_string(),
+                                                                  Type::Int))));
+        assert_eq!(catalog.validate("missing", &[]),
+                   Err(CatalogError::UnknownMethod("missing".to_string())));
+    }
+}
@@ -9,24 +9,947 @@
 // Rust XML-RPC library
 
 use hyper;
+use std::ascii::AsciiExt;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufferedStream, Stream};
+use std::io::net::tcp::TcpStream;
+use std::os;
 use std::string;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::time::Duration;
+
+use rustc_serialize::Decodable;
+use rustc_serialize::base64::{ToBase64, STANDARD};
+use time;
+
+use cache::ResponseCache;
+use faults::{FaultMap, MappedError};
+use limiter::RateLimiter;
+use signing;
+use transport::Connection;
+
+/// The errors that can arise while validating a client URL.
+#[derive(Clone, PartialEq, Show)]
+pub enum UrlError {
+    /// The URL is malformed; carries the parser's description
+    ParseError(string::String),
+    /// The scheme is neither http nor https
+    UnsupportedScheme(string::String),
+    /// The URL does not name a host
+    MissingHost,
+}
+
+/// The errors that can arise while building a client.
+#[derive(Clone, PartialEq, Show)]
+pub enum ConfigError {
+    /// The URL is unusable
+    Url(UrlError),
+    /// A header name is not an HTTP token, or its value spans lines
+    InvalidHeader(string::String),
+    /// The basic auth user name contains a colon
+    InvalidUser(string::String),
+    /// A required environment variable is not set
+    MissingVariable(string::String),
+    /// A proxy was given for an https URL; tunnelling through one is not
+    /// supported, and calls are not sent around it
+    UnsupportedProxy(string::String),
+}
+
+/// A snapshot of a client's call counters. The client keeps no connection
+/// pool, so there are no connection counts to report, and as a client is
+/// used from one thread at a time no call is in flight when it is asked.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub struct ClientStats {
+    /// Calls sent since the client was created, counting a retried call
+    /// once; calls refused before sending are not counted
+    pub calls: usize,
+    /// Total time calls have waited before being sent, on the rate limiter
+    pub waited: Duration,
+}
+
+/// The bodies exchanged in a call that failed in transport or returned a
+/// response which could not be parsed, each cut to the client's capture limit.
+#[derive(Clone, PartialEq, Show)]
+pub struct FailedCall {
+    pub request: string::String,
+    /// The response body, if one was read
+    pub response: Option<string::String>,
+    /// A description of the failure
+    pub error: string::String,
+}
+
+/// The number of bytes of each body kept for a failed call, unless the
+/// caller chooses another limit.
+pub const DEFAULT_CAPTURE_LIMIT: usize = 64 * 1024;
 
 pub struct Client {
     url: string::String,
+    parsed: hyper::Url,
+    cache: Option<RefCell<ResponseCache>>,
+    overrides: HashMap<string::String, string::String>,
+    resolver: Option<Box<Fn(&str) -> Option<string::String> + 'static>>,
+    connection: Option<RefCell<Box<Connection + 'static>>>,
+    signer: Option<(string::String, Box<Fn(&str) -> string::String + 'static>)>,
+    request_id_header: Option<string::String>,
+    limiter: Option<RateLimiter>,
+    idempotent: HashSet<string::String>,
+    retries: usize,
+    deadline_header: Option<string::String>,
+    user_agent: string::String,
+    accept: Option<string::String>,
+    headers: Vec<(string::String, string::String)>,
+    http_proxy: Option<string::String>,
+    capture_limit: usize,
+    last_failure: RefCell<Option<FailedCall>>,
+    calls: Cell<usize>,
+    waited_ns: Cell<u64>,
+}
+
+/// The User-Agent sent unless the caller chooses another.
+pub const DEFAULT_USER_AGENT: &'static str = concat!("rust-xmlrpc/", env!("CARGO_PKG_VERSION"));
+
+static NEXT_REQUEST_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Generates an ID unique within this process and unlikely to collide
+/// with other processes.
+fn generate_request_id() -> string::String {
+    let n = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+    format!("{:x}-{:x}", time::precise_time_ns(), n)
 }
 
 impl Client {
-    pub fn new(s: &str) -> Client {
-        Client { url: s.to_string() }
+    /// Creates a client for the server at URL `s`, which is validated
+    /// immediately. IPv6 hosts are written in brackets, e.g. `http://[::1]:8000/`.
+    pub fn new(s: &str) -> Result<Client, UrlError> {
+        let parsed = match hyper::Url::parse(s) {
+            Ok(url) => url,
+            Err(e) => return Err(UrlError::ParseError(format!("{:?}", e))),
+        };
+        match parsed.scheme.as_slice() {
+            "http" | "https" => {}
+            scheme => return Err(UrlError::UnsupportedScheme(scheme.to_string())),
+        }
+        if parsed.host().is_none() {
+            return Err(UrlError::MissingHost);
+        }
+        Ok(Client {
+            url: s.to_string(),
+            parsed: parsed,
+            cache: None,
+            overrides: HashMap::new(),
+            resolver: None,
+            connection: None,
+            signer: None,
+            request_id_header: None,
+            limiter: None,
+            idempotent: HashSet::new(),
+            retries: 0,
+            deadline_header: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            accept: None,
+            headers: Vec::new(),
+            http_proxy: None,
+            capture_limit: DEFAULT_CAPTURE_LIMIT,
+            last_failure: RefCell::new(None),
+            calls: Cell::new(0),
+            waited_ns: Cell::new(0),
+        })
+    }
+
+    /// Signs each request body with `signer`, typically an HMAC over a
+    /// shared secret, sending the signature in the header named `header`.
+    pub fn sign_with<F>(mut self, header: &str, signer: F) -> Client where
+        F: Fn(&str) -> string::String + 'static,
+    {
+        self.signer = Some((header.to_string(), Box::new(signer)));
+        self
+    }
+
+    /// Signs each request body with HMAC-SHA256 under `key`, sending the
+    /// signature in hex in the header named `header`. Servers check it with
+    /// `signing::verify`.
+    pub fn sign_hmac(self, header: &str, key: &[u8]) -> Client {
+        let key = key.to_vec();
+        self.sign_with(header, move |body: &str| signing::sign(key.as_slice(), body))
+    }
+
+    /// Sends the ID of each call in the header named `header`, usually
+    /// "X-Request-Id", generating one for requests that do not carry an ID.
+    pub fn with_request_id(mut self, header: &str) -> Client {
+        self.request_id_header = Some(header.to_string());
+        self
+    }
+
+    /// Sends `user_agent` as the User-Agent header in place of the default.
+    pub fn user_agent(mut self, user_agent: &str) -> Client {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Sends `accept` as the Accept header; none is sent by default.
+    pub fn accept(mut self, accept: &str) -> Client {
+        self.accept = Some(accept.to_string());
+        self
+    }
+
+    /// Sends the header `name` with every call. Headers are checked as each
+    /// call is sent, and a call with a header that is not a valid HTTP
+    /// header fails without being sent.
+    pub fn header(mut self, name: &str, value: &str) -> Client {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Creates a client for the ROS master named by `ROS_MASTER_URI`,
+    /// honouring the proxy variables as `ClientBuilder::proxy_from_env` does.
+    pub fn from_env() -> Result<Client, ConfigError> {
+        match os::getenv("ROS_MASTER_URI") {
+            Some(url) => ClientBuilder::new(url.as_slice()).proxy_from_env().build(),
+            None => Err(ConfigError::MissingVariable("ROS_MASTER_URI".to_string())),
+        }
+    }
+
+    /// Returns a builder for a client of the server at URL `url`.
+    pub fn builder(url: &str) -> ClientBuilder {
+        ClientBuilder::new(url)
+    }
+
+    /// Keeps at most `bytes` of each body for failed calls.
+    pub fn capture_limit(mut self, bytes: usize) -> Client {
+        self.capture_limit = bytes;
+        self
+    }
+
+    /// Returns the bodies exchanged in the most recent failed call, if any.
+    pub fn last_failure(&self) -> Option<FailedCall> {
+        self.last_failure.borrow().clone()
+    }
+
+    /// Marks `method` as safe to repeat. Only marked methods are answered
+    /// from the response cache or retried.
+    pub fn idempotent(mut self, method: &str) -> Client {
+        self.idempotent.insert(method.to_string());
+        self
+    }
+
+    /// Retries a call up to `retries` times when it fails in transport.
+    /// Only calls known to be safe to repeat are retried: those to methods
+    /// marked idempotent and those carrying an idempotency key.
+    pub fn retries(mut self, retries: usize) -> Client {
+        self.retries = retries;
+        self
+    }
+
+    /// Sends the time left before each request's deadline, in milliseconds,
+    /// in the header named `header`, so the server can give up on calls
+    /// whose caller has. Calls whose deadline has already passed fail
+    /// without being sent, whether or not the header is enabled.
+    pub fn send_deadline(mut self, header: &str) -> Client {
+        self.deadline_header = Some(header.to_string());
+        self
+    }
+
+    /// Returns true if `method` has been marked idempotent.
+    pub fn is_idempotent(&self, method: &str) -> bool {
+        self.idempotent.contains(method)
+    }
+
+    /// Throttles calls through `limiter`, waiting as needed before each one
+    /// is sent. Calls answered from the response cache are not throttled.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Client {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Creates a client which sends every call over `stream`, a connection
+    /// the caller has already established (through a SOCKS proxy, a TLS
+    /// tunnel, a forwarded port...). The Host header and path are taken
+    /// from `url`; no connection is made to it.
+    pub fn from_stream<S: Stream + 'static>(stream: S, url: &str) -> Result<Client, UrlError> {
+        let mut client = try!(Client::new(url));
+        let connection = Box::new(BufferedStream::new(stream)) as Box<Connection + 'static>;
+        client.connection = Some(RefCell::new(connection));
+        Ok(client)
+    }
+
+    /// Returns the URL the client was created with.
+    pub fn url(&self) -> &str {
+        self.url.as_slice()
+    }
+
+    /// Returns the URL scheme, "http" or "https".
+    pub fn scheme(&self) -> &str {
+        self.parsed.scheme.as_slice()
+    }
+
+    /// Returns the host, with IPv6 addresses in brackets.
+    pub fn host(&self) -> string::String {
+        self.parsed.host().unwrap().serialize()
+    }
+
+    /// Returns the port, or the scheme's default port if none was given.
+    pub fn port(&self) -> u16 {
+        self.parsed.port_or_default().unwrap()
+    }
+
+    /// Returns the path of the XML-RPC endpoint.
+    pub fn path(&self) -> string::String {
+        self.parsed.serialize_path().unwrap_or("/".to_string())
+    }
+
+    /// Connects to `addr` (as "host:port") whenever the URL names `host`.
+    /// The request is still addressed to `host` in its Host header.
+    pub fn resolve(mut self, host: &str, addr: &str) -> Client {
+        self.overrides.insert(host.to_string(), addr.to_string());
+        self
+    }
+
+    /// Consults `resolver` for each host not covered by `resolve`. It
+    /// returns the "host:port" to connect to, or None to use the host as is.
+    pub fn resolver<F>(mut self, resolver: F) -> Client where
+        F: Fn(&str) -> Option<string::String> + 'static,
+    {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Returns the counts of the client's calls.
+    pub fn stats(&self) -> ClientStats {
+        ClientStats {
+            calls: self.calls.get(),
+            waited: Duration::nanoseconds(self.waited_ns.get() as i64),
+        }
+    }
+
+    /// Caches up to `capacity` successful responses for `ttl`, so repeated
+    /// identical calls to methods marked idempotent are answered locally.
+    pub fn with_cache(mut self, capacity: usize, ttl: Duration) -> Client {
+        self.cache = Some(RefCell::new(ResponseCache::new(capacity, ttl)));
+        self
+    }
+
+    /// Performs the call and decodes the first parameter of the response,
+    /// turning faults into application errors through `faults`.
+    pub fn call<T, E>(&self, request: &super::Request, faults: &FaultMap<E>)
+                      -> Option<Result<T, MappedError<E>>> where
+        T: Decodable,
+    {
+        self.remote_call(request).map(|response| faults.result(&response, 0))
     }
 
     pub fn remote_call(&self, request: &super::Request) -> Option<super::Response> {
+        let cache = match self.cache {
+            Some(ref cache) if self.is_idempotent(request.method.as_slice()) => cache,
+            _ => return self.remote_call_uncached(request),
+        };
+        if let Some(response) = cache.borrow_mut().get(request) {
+            return Some(response);
+        }
+        let response = self.remote_call_uncached(request);
+        if let Some(ref response) = response {
+            cache.borrow_mut().insert(request, response);
+        }
+        response
+    }
+
+    /// Performs the call without consulting or filling the response cache.
+    pub fn remote_call_uncached(&self, request: &super::Request) -> Option<super::Response> {
+        if let Some(e) = request.error() {
+            self.record_failure(request.document().as_slice(), None,
+                                format!("invalid request: {:?}", e));
+            return None;
+        }
+        if request.remaining() == Some(Duration::zero()) {
+            self.record_failure(request.document().as_slice(), None,
+                                "deadline exceeded".to_string());
+            return None;
+        }
+        // user agents, signatures and per-request headers all pass through
+        // here, so a CR or LF cannot reach the wire from any of them
+        let body = request.document();
+        // one ID serves every attempt, so retries can be matched in server logs
+        let id = match request.request_id {
+            Some(ref id) => id.clone(),
+            None => generate_request_id(),
+        };
+        for &(ref name, ref value) in self.extra_headers(request, body.as_slice(), id.as_slice()).iter() {
+            if let Err(e) = check_header(name.as_slice(), value.as_slice()) {
+                self.record_failure(body.as_slice(), None, format!("{:?}", e));
+                return None;
+            }
+        }
+        if let Some(ref limiter) = self.limiter {
+            let waiting = time::precise_time_ns();
+            limiter.acquire(request.method.as_slice());
+            let waited = time::precise_time_ns() - waiting;
+            self.waited_ns.set(self.waited_ns.get() + waited);
+        }
+        let start = time::precise_time_ns();
+        self.calls.set(self.calls.get() + 1);
+        let mut response = self.send(request, id.as_slice());
+        let safe = self.is_idempotent(request.method.as_slice()) || request.idempotency_key.is_some();
+        let mut retries = if safe { self.retries } else { 0 };
+        while response.is_none() && retries > 0 {
+            retries -= 1;
+            response = self.send(request, id.as_slice());
+        }
+        if let Some(ref response) = response {
+            if let Err(super::ResponseError::ParseError(e)) = response.value(0) {
+                self.record_failure(request.document().as_slice(),
+                                    Some(response.body.as_slice()), format!("{:?}", e));
+            }
+        }
+        trace_call(request.method.as_slice(), start, &response);
+        response
+    }
+
+    fn send(&self, request: &super::Request, id: &str) -> Option<super::Response> {
+        let body = request.document();
+        let extra_headers = self.extra_headers(request, body.as_slice(), id);
+        let result = match (&self.connection, &self.http_proxy) {
+            (&Some(ref connection), _) => {
+                Some(connection.borrow_mut().post(self.request_target().as_slice(),
+                                                  self.host_header().as_slice(),
+                                                  extra_headers.as_slice(), body.as_slice()))
+            }
+            // a proxy is sent the full URL in place of the path, over a
+            // connection used for this call only
+            (&None, &Some(ref proxy)) if self.scheme() == "http" => {
+                let mut extra_headers = extra_headers.clone();
+                extra_headers.push(("Connection".to_string(), "close".to_string()));
+                Some(TcpStream::connect(proxy.as_slice()).and_then(|stream| {
+                    BufferedStream::new(stream).post(self.url.as_slice(),
+                                                     self.host_header().as_slice(),
+                                                     extra_headers.as_slice(), body.as_slice())
+                }))
+            }
+            _ => None,
+        };
+        if let Some(result) = result {
+            return match result {
+                Ok(ref http) if http.status != 200 => {
+                    self.record_failure(body.as_slice(), Some(http.body.as_slice()),
+                                        format!("unexpected HTTP status {}", http.status));
+                    None
+                }
+                Ok(http) => {
+                    let mut response = super::Response::new(http.body.as_slice());
+                    response.headers = http.headers;
+                    Some(response)
+                }
+                Err(e) => {
+                    self.record_failure(body.as_slice(), None, e.to_string());
+                    None
+                }
+            };
+        }
+
         let mut http_client = hyper::Client::new();
-        let mut result = http_client.post(self.url.as_slice())
-            .body(request.body.as_slice()) // FIXME: use to_xml() somehow?
+        let mut headers = hyper::header::Headers::new();
+        let (url, host) = self.target();
+        if let Some(host) = host {
+            headers.set(host);
+        }
+        for (name, value) in extra_headers.into_iter() {
+            headers.set_raw(name, vec![value.into_bytes()]);
+        }
+        let result = http_client.post(url.as_slice())
+            .headers(headers)
+            .body(body.as_slice())
             .send();
-        let body = result.ok().unwrap().read_to_string().unwrap();
-        //println!("{}", response.unwrap());
-        Some(super::Response::new(body.as_slice())) // FIXME: change to a Result<> type
+        let mut response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.record_failure(body.as_slice(), None, format!("{:?}", e));
+                return None;
+            }
+        };
+        let headers = response.headers.iter()
+            .map(|header| (header.name().to_string(), header.value_string()))
+            .collect();
+        let response_body = match response.read_to_string() {
+            Ok(response_body) => response_body,
+            Err(e) => {
+                self.record_failure(body.as_slice(), None, e.to_string());
+                return None;
+            }
+        };
+        if response.status != hyper::status::StatusCode::Ok {
+            self.record_failure(body.as_slice(), Some(response_body.as_slice()),
+                                format!("unexpected HTTP status {}", response.status));
+            return None;
+        }
+        let mut response = super::Response::new(response_body.as_slice()); // FIXME: change to a Result<> type
+        response.headers = headers;
+        Some(response)
+    }
+
+    fn record_failure(&self, request: &str, response: Option<&str>, error: string::String) {
+        *self.last_failure.borrow_mut() = Some(FailedCall {
+            request: truncate(request, self.capture_limit).to_string(),
+            response: response.map(|body| truncate(body, self.capture_limit).to_string()),
+            error: error,
+        });
+    }
+
+    /// Returns the headers to send with `request` beyond those describing
+    /// the connection and body, identifying the call by `id`.
+    fn extra_headers(&self, request: &super::Request, body: &str, id: &str)
+                     -> Vec<(string::String, string::String)> {
+        let mut headers = vec![("User-Agent".to_string(), self.user_agent.clone())];
+        if let Some(ref accept) = self.accept {
+            headers.push(("Accept".to_string(), accept.clone()));
+        }
+        headers.extend(self.headers.iter().cloned());
+        if let Some(ref key) = request.idempotency_key {
+            headers.push(("Idempotency-Key".to_string(), key.clone()));
+        }
+        if let Some(ref header) = self.request_id_header {
+            headers.push((header.clone(), id.to_string()));
+        }
+        if let (Some(header), Some(remaining)) = (self.deadline_header.as_ref(),
+                                                 request.remaining()) {
+            headers.push((header.to_string(), remaining.num_milliseconds().to_string()));
+        }
+        if let Some((ref header, ref signer)) = self.signer {
+            headers.push((header.clone(), (**signer)(body)));
+        }
+        for &(ref name, ref value) in request.headers.iter() {
+            headers.retain(|&(ref n, _)| !n.as_slice().eq_ignore_ascii_case(name.as_slice()));
+            headers.push((name.clone(), value.clone()));
+        }
+        headers
+    }
+
+    /// Returns the Host header value, with the port if the URL gives one.
+    fn host_header(&self) -> string::String {
+        match self.parsed.port() {
+            Some(port) => format!("{}:{}", self.host(), port),
+            None => self.host(),
+        }
+    }
+
+    /// Returns the URL to connect to and, if a host override applies, the
+    /// Host header naming the original host.
+    fn target(&self) -> (string::String, Option<hyper::header::Host>) {
+        let url = &self.parsed;
+        let domain = match url.domain() {
+            Some(domain) => domain.to_string(),
+            None => return (self.url.clone(), None),
+        };
+        let addr = match self.overrides.get(&domain) {
+            Some(addr) => addr.clone(),
+            None => match self.resolver {
+                Some(ref resolver) => match (**resolver)(domain.as_slice()) {
+                    Some(addr) => addr,
+                    None => return (self.url.clone(), None),
+                },
+                None => return (self.url.clone(), None),
+            },
+        };
+        let target = format!("{}://{}{}", url.scheme, addr, self.request_target());
+        (target, Some(hyper::header::Host { hostname: domain, port: url.port() }))
+    }
+
+    /// Returns the path with the query string, if any, as sent in the
+    /// request line.
+    fn request_target(&self) -> string::String {
+        match self.parsed.query {
+            Some(ref query) => format!("{}?{}", self.path(), query),
+            None => self.path(),
+        }
+    }
+}
+
+/// Collects the settings of a client, checking them all when the client
+/// is built. There are no connection timeouts or TLS settings, as hyper
+/// exposes neither; `Request::with_timeout` bounds how long a call is
+/// wanted, and https uses hyper's defaults.
+pub struct ClientBuilder {
+    url: string::String,
+    basic_auth: Option<(string::String, string::String)>,
+    headers: Vec<(string::String, string::String)>,
+    user_agent: Option<string::String>,
+    accept: Option<string::String>,
+    request_id_header: Option<string::String>,
+    cache: Option<(usize, Duration)>,
+    limiter: Option<RateLimiter>,
+    capture_limit: Option<usize>,
+    http_proxy: Option<string::String>,
+    idempotent: Vec<string::String>,
+    retries: usize,
+}
+
+impl ClientBuilder {
+    /// Starts a builder for a client of the server at URL `url`.
+    pub fn new(url: &str) -> ClientBuilder {
+        ClientBuilder {
+            url: url.to_string(),
+            basic_auth: None,
+            headers: Vec::new(),
+            user_agent: None,
+            accept: None,
+            request_id_header: None,
+            cache: None,
+            limiter: None,
+            capture_limit: None,
+            http_proxy: None,
+            idempotent: Vec::new(),
+            retries: 0,
+        }
+    }
+
+    /// Sends http calls through the proxy at `addr`, given as "host:port".
+    /// Proxying https calls is not supported, and `build` fails if the URL
+    /// is https.
+    pub fn http_proxy(mut self, addr: &str) -> ClientBuilder {
+        self.http_proxy = Some(addr.to_string());
+        self
+    }
+
+    /// Takes the proxy for an http URL from `http_proxy` (or `HTTP_PROXY`),
+    /// unless the host is excluded by `no_proxy` (or `NO_PROXY`), a
+    /// comma-separated list of host names and domain suffixes, or `*` for
+    /// every host. https calls cannot be proxied, so for an https URL
+    /// `https_proxy` is ignored and calls are made directly.
+    pub fn proxy_from_env(mut self) -> ClientBuilder {
+        let url = hyper::Url::parse(self.url.as_slice()).ok();
+        let http = url.as_ref().map(|url| url.scheme.as_slice() == "http").unwrap_or(false);
+        let proxy = match getenv_either("http_proxy", "HTTP_PROXY") {
+            Some(ref proxy) if http => proxy.clone(),
+            _ => return self,
+        };
+        let host = url.as_ref().and_then(|url| url.domain().map(|domain| domain.to_string()));
+        let no_proxy = getenv_either("no_proxy", "NO_PROXY").unwrap_or(string::String::new());
+        if let Some(host) = host {
+            if excluded_from_proxy(host.as_slice(), no_proxy.as_slice()) {
+                return self;
+            }
+        }
+        // the variable is usually a URL, though a bare "host:port" is common
+        let addr = match hyper::Url::parse(proxy.as_slice()) {
+            Ok(ref url) if url.domain().is_some() => {
+                format!("{}:{}", url.domain().unwrap(), url.port_or_default().unwrap_or(80))
+            }
+            _ => proxy.as_slice().trim_right_matches('/').to_string(),
+        };
+        self.http_proxy = Some(addr);
+        self
+    }
+
+    /// Authenticates every call with HTTP basic auth.
+    pub fn basic_auth(mut self, user: &str, password: &str) -> ClientBuilder {
+        self.basic_auth = Some((user.to_string(), password.to_string()));
+        self
+    }
+
+    /// Sends the header `name` with every call.
+    pub fn header(mut self, name: &str, value: &str) -> ClientBuilder {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sends `user_agent` as the User-Agent header in place of the default.
+    pub fn user_agent(mut self, user_agent: &str) -> ClientBuilder {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Sends `accept` as the Accept header.
+    pub fn accept(mut self, accept: &str) -> ClientBuilder {
+        self.accept = Some(accept.to_string());
+        self
+    }
+
+    /// Sends the ID of each call in the header named `header`.
+    pub fn request_id(mut self, header: &str) -> ClientBuilder {
+        self.request_id_header = Some(header.to_string());
+        self
+    }
+
+    /// Caches up to `capacity` successful responses for `ttl`, for methods
+    /// marked idempotent.
+    pub fn cache(mut self, capacity: usize, ttl: Duration) -> ClientBuilder {
+        self.cache = Some((capacity, ttl));
+        self
+    }
+
+    /// Throttles calls through `limiter`.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> ClientBuilder {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Marks `method` as safe to repeat; see `Client::idempotent`.
+    pub fn idempotent(mut self, method: &str) -> ClientBuilder {
+        self.idempotent.push(method.to_string());
+        self
+    }
+
+    /// Retries calls safe to repeat; see `Client::retries`.
+    pub fn retries(mut self, retries: usize) -> ClientBuilder {
+        self.retries = retries;
+        self
+    }
+
+    /// Keeps at most `bytes` of each body for failed calls.
+    pub fn capture_limit(mut self, bytes: usize) -> ClientBuilder {
+        self.capture_limit = Some(bytes);
+        self
+    }
+
+    /// Checks the settings and creates the client.
+    pub fn build(self) -> Result<Client, ConfigError> {
+        let mut client = try!(Client::new(self.url.as_slice()).map_err(ConfigError::Url));
+        for &(ref name, ref value) in self.headers.iter() {
+            try!(check_header(name.as_slice(), value.as_slice()));
+        }
+        client.headers = self.headers;
+        if let Some((user, password)) = self.basic_auth {
+            if user.contains(":") {
+                return Err(ConfigError::InvalidUser(user));
+            }
+            let credentials = format!("{}:{}", user, password).as_bytes().to_base64(STANDARD);
+            client.headers.push(("Authorization".to_string(), format!("Basic {}", credentials)));
+        }
+        if let Some(user_agent) = self.user_agent {
+            try!(check_header("User-Agent", user_agent.as_slice()));
+            client.user_agent = user_agent;
+        }
+        if let Some(accept) = self.accept {
+            try!(check_header("Accept", accept.as_slice()));
+            client.accept = Some(accept);
+        }
+        if let Some(header) = self.request_id_header {
+            try!(check_header(header.as_slice(), ""));
+            client.request_id_header = Some(header);
+        }
+        if let Some((capacity, ttl)) = self.cache {
+            client = client.with_cache(capacity, ttl);
+        }
+        client.limiter = self.limiter;
+        if let Some(bytes) = self.capture_limit {
+            client.capture_limit = bytes;
+        }
+        if let Some(ref proxy) = self.http_proxy {
+            if client.scheme() != "http" {
+                return Err(ConfigError::UnsupportedProxy(proxy.clone()));
+            }
+        }
+        client.http_proxy = self.http_proxy;
+        client.idempotent = self.idempotent.into_iter().collect();
+        client.retries = self.retries;
+        Ok(client)
+    }
+}
+
+fn getenv_either(lower: &str, upper: &str) -> Option<string::String> {
+    os::getenv(lower).or_else(|| os::getenv(upper)).and_then(|value| {
+        if value.as_slice().trim().is_empty() { None } else { Some(value) }
+    })
+}
+
+/// Returns true if `no_proxy` names `host` or a domain containing it.
+fn excluded_from_proxy(host: &str, no_proxy: &str) -> bool {
+    no_proxy.split(',').map(|entry| entry.trim()).any(|entry| {
+        let suffix = entry.trim_left_matches('.');
+        entry == "*" || (!suffix.is_empty() &&
+                         (host == suffix || host.ends_with(format!(".{}", suffix).as_slice())))
+    })
+}
+
+/// Checks that `name` is an HTTP token and `value` fits on one line.
+fn check_header(name: &str, value: &str) -> Result<(), ConfigError> {
+    let separators = "()<>@,;:\\\"/[]?={} \t";
+    let name_ok = !name.is_empty() &&
+        name.chars().all(|c| c > ' ' && c < '\x7f' && !separators.contains_char(c));
+    let value_ok = !value.chars().any(|c| c == '\r' || c == '\n');
+    if name_ok && value_ok { Ok(()) } else { Err(ConfigError::InvalidHeader(name.to_string())) }
+}
+
+/// Returns at most the first `limit` bytes of `s`, cut at a character boundary.
+fn truncate(s: &str, limit: usize) -> &str {
+    if s.len() <= limit {
+        return s;
+    }
+    let mut end = limit;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Logs the method, duration and outcome of a call.
+#[cfg(feature = "logging")]
+fn trace_call(method: &str, start: u64, response: &Option<super::Response>) {
+    let elapsed_ms = (time::precise_time_ns() - start) / 1_000_000;
+    match *response {
+        Some(ref response) if response.is_fault() => {
+            warn!("xmlrpc call {} returned a fault after {} ms", method, elapsed_ms)
+        }
+        Some(_) => debug!("xmlrpc call {} succeeded after {} ms", method, elapsed_ms),
+        None => warn!("xmlrpc call {} failed after {} ms", method, elapsed_ms),
+    }
+}
+
+#[cfg(not(feature = "logging"))]
+fn trace_call(_method: &str, _start: u64, _response: &Option<super::Response>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Client, ClientBuilder, ConfigError};
+    use fixtures;
+    use protocol::Request;
+    use signing;
+    use std::ascii::AsciiExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::string;
+    use std::collections::BTreeMap;
+    use std::os;
+    use std::time::Duration;
+    use transport::{MockStream, http_response};
+
+    // nothing listens on port 1, so a call that is sent fails in transport
+    const UNREACHABLE: &'static str = "http://127.0.0.1:1/RPC2";
+
+    // a client whose server answers each call with the next of `results`
+    fn mock_client(url: &str, results: &[i32]) -> (Client, Rc<RefCell<Vec<u8>>>) {
+        let responses: Vec<string::String> = results.iter()
+            .map(|n| http_response(200, fixtures::response_ok(n).as_slice()))
+            .collect();
+        let (stream, written) = MockStream::new(responses.concat().as_slice());
+        (Client::from_stream(stream, url).unwrap(), written)
+    }
+
+    // the requests written to a mock stream, head and body together
+    fn sent(written: &Rc<RefCell<Vec<u8>>>) -> Vec<string::String> {
+        let sent = string::String::from_utf8(written.borrow().clone()).unwrap();
+        sent.as_slice().split_str("POST ").skip(1).map(|s| format!("POST {}", s)).collect()
+    }
+
+    #[test]
+    fn test_cache_is_for_idempotent_methods() {
+        let (client, written) = mock_client("http://server/RPC2", &[1, 2, 3]);
+        let client = client.with_cache(8, Duration::minutes(1)).idempotent("lookup");
+        let lookup = Request::new("lookup").argument(&1i32).finalize();
+        assert_eq!(client.remote_call(&lookup).unwrap().result::<i32>(0), Ok(1));
+        assert_eq!(client.remote_call(&lookup).unwrap().result::<i32>(0), Ok(1));
+        let update = Request::new("update").argument(&1i32).finalize();
+        assert_eq!(client.remote_call(&update).unwrap().result::<i32>(0), Ok(2));
+        assert_eq!(client.remote_call(&update).unwrap().result::<i32>(0), Ok(3));
+        assert_eq!(sent(&written).len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_method_name_is_not_sent() {
+        let client = Client::new(UNREACHABLE).unwrap();
+        let request = Request::new("bad name<").argument(&1i32);
+        assert!(client.remote_call(&request).is_none());
+        let failure = client.last_failure().unwrap();
+        assert_eq!(failure.error.as_slice(), "invalid request: InvalidMethodName");
+    }
+
+    #[test]
+    fn test_unencodable_argument_is_not_sent() {
+        // a map key must be a scalar
+        let mut map = BTreeMap::new();
+        map.insert(vec![1i32], 2i32);
+        let client = Client::new(UNREACHABLE).unwrap();
+        let request = Request::new("echo").argument(&map).argument(&3i32);
+        assert!(client.remote_call(&request).is_none());
+        let failure = client.last_failure().unwrap();
+        assert_eq!(failure.error.as_slice(), "invalid request: NotScalar");
+    }
+
+    #[test]
+    fn test_https_proxy_is_refused() {
+        let built = ClientBuilder::new("https://example.com/RPC2").http_proxy("proxy:3128").build();
+        assert_eq!(built.err(), Some(ConfigError::UnsupportedProxy("proxy:3128".to_string())));
+        assert!(ClientBuilder::new("http://example.com/RPC2").http_proxy("proxy:3128").build().is_ok());
+    }
+
+    #[test]
+    fn test_https_ignores_env_proxy() {
+        os::setenv("https_proxy", "http://proxy:3128/");
+        let built = ClientBuilder::new("https://example.com/RPC2").proxy_from_env().build();
+        os::unsetenv("https_proxy");
+        assert!(built.unwrap().http_proxy.is_none());
+    }
+
+    #[test]
+    fn test_request_headers_override_client_headers() {
+        let client = Client::new(UNREACHABLE).unwrap()
+            .header("X-Trace", "0")
+            .header("X-Team", "nav");
+        let request = Request::new("ping").with_header("x-trace", "1").with_header("X-Token", "t");
+        let headers = client.extra_headers(&request, "", "id");
+        assert_eq!(values(headers.as_slice(), "X-Trace"), vec!["1"]);
+        assert_eq!(values(headers.as_slice(), "X-Team"), vec!["nav"]);
+        assert_eq!(values(headers.as_slice(), "X-Token"), vec!["t"]);
+        assert_eq!(values(headers.as_slice(), "User-Agent").len(), 1);
+    }
+
+    #[test]
+    fn test_sign_hmac() {
+        let client = Client::new(UNREACHABLE).unwrap().sign_hmac("X-Signature", b"secret");
+        let body = "<methodCall><methodName>ping</methodName></methodCall>";
+        let headers = client.extra_headers(&Request::new("ping"), body, "id");
+        let signature = values(headers.as_slice(), "X-Signature");
+        assert_eq!(signature.len(), 1);
+        assert!(signing::verify(b"secret", body, signature[0]));
+    }
+
+    fn values<'a>(headers: &'a [(string::String, string::String)], name: &str) -> Vec<&'a str> {
+        headers.iter().filter(|&&(ref n, _)| n.as_slice().eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_invalid_header_is_not_sent() {
+        let client = Client::new(UNREACHABLE).unwrap();
+        let request = Request::new("ping").with_header("X-Trace", "1\r\nX-Injected: 1");
+        assert!(client.remote_call(&request).is_none());
+        let failure = client.last_failure().unwrap();
+        assert!(failure.error.as_slice().starts_with("InvalidHeader"));
+        assert_eq!(client.stats().calls, 0);
+    }
+
+    #[test]
+    fn test_retries_share_request_id() {
+        let ok = http_response(200, fixtures::response_ok(&1i32).as_slice());
+        let responses = format!("{}{}{}", http_response(500, "busy"), ok, ok);
+        let (stream, written) = MockStream::new(responses.as_slice());
+        let client = Client::from_stream(stream, "http://server/RPC2").unwrap()
+            .with_request_id("X-Request-Id").idempotent("lookup").retries(1);
+        let request = Request::new("lookup").finalize();
+        assert!(client.remote_call(&request).is_some());
+        assert!(client.remote_call(&request).is_some());
+
+        let ids: Vec<string::String> = sent(&written).iter().map(|request| {
+            let line = request.as_slice().lines_any().find(|line| line.starts_with("X-Request-Id: "));
+            line.unwrap()["X-Request-Id: ".len()..].to_string()
+        }).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[0], ids[1]);
+        assert!(ids[1] != ids[2]);
+    }
+
+    #[test]
+    fn test_stream_request_keeps_query() {
+        let (client, written) = mock_client("http://server/RPC2?token=abc", &[1]);
+        assert!(client.remote_call(&Request::new("ping").finalize()).is_some());
+        assert!(sent(&written)[0].as_slice().starts_with("POST /RPC2?token=abc HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_error_status_is_a_failure() {
+        let (stream, _) = MockStream::new(http_response(500, "<html>boom</html>").as_slice());
+        let client = Client::from_stream(stream, "http://server/RPC2").unwrap();
+        assert!(client.remote_call(&Request::new("ping").finalize()).is_none());
+        let failure = client.last_failure().unwrap();
+        assert_eq!(failure.error.as_slice(), "unexpected HTTP status 500");
+        assert_eq!(failure.response, Some("<html>boom</html>".to_string()));
     }
 }
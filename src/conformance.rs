@@ -0,0 +1,97 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+//! Documents paired with the values they must parse to, covering every
+//! type in the spec. The crate checks its own parser against this corpus;
+//! transports and bridges built on it can run the same checks.
+
+use std::collections::BTreeMap;
+
+use datetime::DateTime;
+use encoding::Xml;
+
+/// A document and the value it must parse to.
+pub struct Case {
+    pub name: &'static str,
+    pub document: &'static str,
+    pub expected: Xml,
+}
+
+fn case(name: &'static str, document: &'static str, expected: Xml) -> Case {
+    Case { name: name, document: document, expected: expected }
+}
+
+fn object(members: Vec<(&str, Xml)>) -> Xml {
+    let mut map = BTreeMap::new();
+    for (name, value) in members.into_iter() {
+        map.insert(name.to_string(), value);
+    }
+    Xml::Object(map)
+}
+
+/// Returns the corpus.
+pub fn corpus() -> Vec<Case> {
+    vec![
+        case("int", "<int>42</int>", Xml::I32(42)),
+        case("i4", "<i4>-7</i4>", Xml::I32(-7)),
+        case("int max", "<int>2147483647</int>", Xml::I32(2147483647)),
+        case("int min", "<int>-2147483648</int>", Xml::I32(-2147483648)),
+        case("boolean true", "<boolean>1</boolean>", Xml::Boolean(true)),
+        case("boolean false", "<boolean>0</boolean>", Xml::Boolean(false)),
+        case("double", "<double>-12.5</double>", Xml::F64(-12.5)),
+        case("string", "<string>South Dakota</string>", Xml::String("South Dakota".to_string())),
+        case("string escapes", "<string>a &lt;b&gt; &amp; &quot;c&quot; &apos;d&apos;</string>",
+             Xml::String("a <b> & \"c\" 'd'".to_string())),
+        case("string character reference", "<string>caf&#xE9;</string>",
+             Xml::String("caf\u{e9}".to_string())),
+        case("empty string", "<string></string>", Xml::String("".to_string())),
+        case("dateTime", "<dateTime.iso8601>19980717T14:08:55</dateTime.iso8601>",
+             Xml::DateTime(DateTime::new(1998, 7, 17, 14, 8, 55).unwrap())),
+        case("base64", "<base64>aGVsbG8=</base64>", Xml::Base64(b"hello".to_vec())),
+        case("nil", "<nil/>", Xml::Null),
+        case("empty array", "<array><data></data></array>", Xml::Array(vec![])),
+        case("array", "<array><data><value><int>12</int></value>\
+                       <value><string>Egypt</string></value>\
+                       <value><boolean>0</boolean></value>\
+                       <value><int>-31</int></value></data></array>",
+             Xml::Array(vec![Xml::I32(12), Xml::String("Egypt".to_string()),
+                             Xml::Boolean(false), Xml::I32(-31)])),
+        case("untyped value", "<array><data><value>plain</value></data></array>",
+             Xml::Array(vec![Xml::String("plain".to_string())])),
+        case("empty struct", "<struct></struct>", object(vec![])),
+        case("struct", "<struct><member><name>lowerBound</name><value><i4>18</i4></value></member>\
+                        <member><name>upperBound</name><value><i4>139</i4></value></member></struct>",
+             object(vec![("lowerBound", Xml::I32(18)), ("upperBound", Xml::I32(139))])),
+        case("nested", "<struct><member><name>list</name><value><array><data>\
+                        <value><struct><member><name>a</name><value><nil/></value></member>\
+                        </struct></value></data></array></value></member></struct>",
+             object(vec![("list", Xml::Array(vec![object(vec![("a", Xml::Null)])]))])),
+    ]
+}
+
+/// Runs `parse` over the corpus, returning the names of the cases whose
+/// document it failed to parse to the expected value.
+pub fn failures<F>(parse: F) -> Vec<&'static str> where F: Fn(&str) -> Option<Xml> {
+    corpus().into_iter()
+        .filter(|case| parse(case.document) != Some(case.expected.clone()))
+        .map(|case| case.name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::failures;
+    use encoding::Xml;
+
+    #[test]
+    fn test_conformance_corpus() {
+        assert_eq!(failures(|s| Xml::from_str(s).ok()), Vec::<&str>::new());
+    }
+}
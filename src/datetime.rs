@@ -0,0 +1,322 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::string;
+
+use rustc_serialize::{Encodable, Decodable, Encoder, Decoder};
+use time;
+
+use encoding::DATETIME_STRUCT;
+
+/// An XML-RPC dateTime.iso8601 value.
+///
+/// The spec defines no time zone, so `offset` is usually absent and the
+/// value is conventionally taken as UTC. Comparison is by the instant
+/// represented, with an absent offset treated as UTC.
+#[derive(Clone, Copy, Show)]
+pub struct DateTime {
+    year: i32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    offset: Option<i32>,
+}
+
+impl DateTime {
+    /// Creates a dateTime without an offset, or None if any field is out of range.
+    pub fn new(year: i32, month: u32, day: u32,
+               hour: u32, minute: u32, second: u32) -> Option<DateTime> {
+        if year < 0 || year > 9999 || month < 1 || month > 12 || day < 1 ||
+           day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        Some(DateTime {
+            year: year, month: month, day: day,
+            hour: hour, minute: minute, second: second,
+            offset: None,
+        })
+    }
+
+    /// Returns the same wall-clock time at `minutes` east of UTC.
+    pub fn with_offset(mut self, minutes: i32) -> DateTime {
+        self.offset = Some(minutes);
+        self
+    }
+
+    /// Creates a dateTime without an offset from seconds since the Unix
+    /// epoch, or None if the year falls outside the four digits of the
+    /// ISO 8601 form.
+    pub fn from_epoch_seconds(secs: i64) -> Option<DateTime> {
+        let days = div_floor(secs, 86400);
+        let rem = secs - days * 86400;
+        let (year, month, day) = civil_from_days(days);
+        if year < 0 || year > 9999 {
+            return None;
+        }
+        Some(DateTime {
+            year: year as i32, month: month, day: day,
+            hour: (rem / 3600) as u32,
+            minute: (rem % 3600 / 60) as u32,
+            second: (rem % 60) as u32,
+            offset: None,
+        })
+    }
+
+    /// Returns the seconds since the Unix epoch, taking an absent offset as UTC.
+    pub fn to_epoch_seconds(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month, self.day);
+        let secs = days * 86400 + (self.hour * 3600 + self.minute * 60 + self.second) as i64;
+        secs - self.offset.unwrap_or(0) as i64 * 60
+    }
+
+    pub fn from_timespec(ts: time::Timespec) -> Option<DateTime> {
+        DateTime::from_epoch_seconds(ts.sec)
+    }
+
+    pub fn to_timespec(&self) -> time::Timespec {
+        time::Timespec::new(self.to_epoch_seconds(), 0)
+    }
+
+    /// Parses the spec form `19980717T14:08:55`, also accepting dashes in
+    /// the date, no colons in the time, fractional seconds (which are
+    /// dropped) and a trailing `Z` or `+hh:mm` offset.
+    pub fn parse(s: &str) -> Option<DateTime> {
+        let s = s.trim();
+        let t = match s.find('T') {
+            Some(t) => t,
+            None => return None,
+        };
+        let date: string::String = s[..t].chars().filter(|&c| c != '-').collect();
+        let rest = &s[t + 1..];
+        let (time, offset) = match rest.find(is_offset_start) {
+            Some(i) => (&rest[..i], Some(&rest[i..])),
+            None => (rest, None),
+        };
+        // fractional seconds, as in Apache's ex:dateTime, are dropped
+        let time = match time.find('.') {
+            Some(i) if time[i + 1..].chars().all(|c| c.is_digit(10)) => &time[..i],
+            Some(_) => return None,
+            None => time,
+        };
+        let time: string::String = time.chars().filter(|&c| c != ':').collect();
+        if date.len() != 8 || time.len() != 6 ||
+           !date.chars().chain(time.chars()).all(|c| c.is_digit(10)) {
+            return None;
+        }
+        let num = |s: &str| s.parse::<u32>().unwrap();
+        let datetime = DateTime::new(num(&date[0..4]) as i32, num(&date[4..6]), num(&date[6..8]),
+                                     num(&time[0..2]), num(&time[2..4]), num(&time[4..6]));
+        match offset {
+            None => datetime,
+            Some(offset) => match parse_offset(offset) {
+                Some(minutes) => datetime.map(|dt| dt.with_offset(minutes)),
+                None => None,
+            },
+        }
+    }
+
+    pub fn year(&self) -> i32 { self.year }
+    pub fn month(&self) -> u32 { self.month }
+    pub fn day(&self) -> u32 { self.day }
+    pub fn hour(&self) -> u32 { self.hour }
+    pub fn minute(&self) -> u32 { self.minute }
+    pub fn second(&self) -> u32 { self.second }
+
+    /// Returns the offset east of UTC in minutes, if one was given.
+    pub fn offset(&self) -> Option<i32> { self.offset }
+}
+
+fn is_offset_start(c: char) -> bool {
+    c == 'Z' || c == '+' || c == '-'
+}
+
+/// Parses `Z`, `+hh`, `+hhmm` or `+hh:mm` (or with '-') into minutes.
+fn parse_offset(s: &str) -> Option<i32> {
+    if s == "Z" {
+        return Some(0);
+    }
+    let sign = if s.starts_with("-") { -1 } else { 1 };
+    let digits: string::String = s[1..].chars().filter(|&c| c != ':').collect();
+    if !digits.chars().all(|c| c.is_digit(10)) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>(), Some(0)),
+        4 => (digits[..2].parse::<i32>(), digits[2..].parse::<i32>()),
+        _ => return None,
+    };
+    match (hours, minutes) {
+        (Some(h), Some(m)) if h <= 23 && m <= 59 => Some(sign * (h * 60 + m)),
+        _ => None,
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    if a >= 0 { a / b } else { (a - b + 1) / b }
+}
+
+// Conversions between civil dates and days since 1970-01-01, after
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = div_floor(y, 400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = div_floor(z, 146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+impl PartialEq for DateTime {
+    fn eq(&self, other: &DateTime) -> bool {
+        self.to_epoch_seconds() == other.to_epoch_seconds()
+    }
+}
+
+impl Eq for DateTime {}
+
+impl PartialOrd for DateTime {
+    fn partial_cmp(&self, other: &DateTime) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DateTime {
+    fn cmp(&self, other: &DateTime) -> Ordering {
+        self.to_epoch_seconds().cmp(&other.to_epoch_seconds())
+    }
+}
+
+impl fmt::String for DateTime {
+    /// Formats in the spec form, followed by the offset if one was given
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{:04}{:02}{:02}T{:02}:{:02}:{:02}",
+                    self.year, self.month, self.day, self.hour, self.minute, self.second));
+        match self.offset {
+            None => Ok(()),
+            Some(0) => write!(f, "Z"),
+            Some(m) => {
+                let sign = if m < 0 { '-' } else { '+' };
+                let m = if m < 0 { -m } else { m };
+                write!(f, "{}{:02}:{:02}", sign, m / 60, m % 60)
+            }
+        }
+    }
+}
+
+impl Encodable for DateTime {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct(DATETIME_STRUCT, 1, |s| {
+            s.emit_struct_field("iso8601", 0, |s| self.to_string().encode(s))
+        })
+    }
+}
+
+impl Decodable for DateTime {
+    fn decode<D: Decoder>(d: &mut D) -> Result<DateTime, D::Error> {
+        let text = try!(d.read_struct(DATETIME_STRUCT, 1, |d| {
+            d.read_struct_field("iso8601", 0, |d| d.read_str())
+        }));
+        match DateTime::parse(text.as_slice()) {
+            Some(datetime) => Ok(datetime),
+            None => Err(d.error(format!("invalid dateTime.iso8601 {}", text).as_slice())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DateTime;
+
+    #[test]
+    fn test_epoch() {
+        let epoch = DateTime::from_epoch_seconds(0).unwrap();
+        assert_eq!(epoch.to_string().as_slice(), "19700101T00:00:00");
+        assert_eq!(epoch.to_epoch_seconds(), 0);
+
+        let before = DateTime::from_epoch_seconds(-1).unwrap();
+        assert_eq!(before.to_string().as_slice(), "19691231T23:59:59");
+        assert_eq!(before.to_epoch_seconds(), -1);
+    }
+
+    #[test]
+    fn test_leap_years() {
+        assert!(DateTime::new(2000, 2, 29, 0, 0, 0).is_some());
+        assert!(DateTime::new(2012, 2, 29, 0, 0, 0).is_some());
+        assert!(DateTime::new(1900, 2, 29, 0, 0, 0).is_none());
+        assert!(DateTime::new(2015, 2, 29, 0, 0, 0).is_none());
+
+        let leap_day = DateTime::from_epoch_seconds(951782400).unwrap();
+        assert_eq!((leap_day.year(), leap_day.month(), leap_day.day()), (2000, 2, 29));
+        assert_eq!(DateTime::new(2000, 2, 29, 0, 0, 0).unwrap().to_epoch_seconds(), 951782400);
+    }
+
+    #[test]
+    fn test_offsets() {
+        let eastern = DateTime::parse("19980717T14:08:55-05:00").unwrap();
+        assert_eq!(eastern.offset(), Some(-300));
+        assert_eq!(eastern.to_epoch_seconds(), 900702535);
+        assert_eq!(eastern, DateTime::parse("19980717T19:08:55Z").unwrap());
+        assert_eq!(eastern.to_string().as_slice(), "19980717T14:08:55-05:00");
+
+        assert_eq!(DateTime::parse("19980717T14:08:55+0130").unwrap().offset(), Some(90));
+        assert_eq!(DateTime::parse("19980717T14:08:55-02").unwrap().offset(), Some(-120));
+        assert!(DateTime::parse("19980717T14:08:55+24:00").is_none());
+        assert!(DateTime::parse("19980717T14:08:55+1:00").is_none());
+    }
+
+    #[test]
+    fn test_fractional_seconds() {
+        let apache = DateTime::parse("2015-01-02T03:04:05.678Z").unwrap();
+        assert_eq!(apache, DateTime::parse("20150102T03:04:05Z").unwrap());
+        assert!(DateTime::parse("20150102T03:04:05.x").is_none());
+    }
+
+    #[test]
+    fn test_year_range() {
+        let last = DateTime::from_epoch_seconds(253402300799).unwrap();
+        assert_eq!(last.to_string().as_slice(), "99991231T23:59:59");
+        assert!(DateTime::from_epoch_seconds(253402300800).is_none());
+
+        let first = DateTime::from_epoch_seconds(-62167219200).unwrap();
+        assert_eq!(first.to_string().as_slice(), "00000101T00:00:00");
+        assert!(DateTime::from_epoch_seconds(-62167219201).is_none());
+    }
+}
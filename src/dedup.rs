@@ -0,0 +1,166 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::collections::HashMap;
+use std::string;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use client::Client;
+use protocol::{Request, Response};
+
+struct InFlight {
+    result: Mutex<Option<Option<Response>>>,
+    done: Condvar,
+    // the calls waiting on this one, counted as they join it
+    waiters: AtomicUsize,
+}
+
+type Calls = Arc<Mutex<HashMap<string::String, Arc<InFlight>>>>;
+
+/// Completes a call's slot and releases it when the leading call ends,
+/// whether it returns or panics, so that no waiter is left blocked.
+struct Completion {
+    in_flight: Calls,
+    key: string::String,
+    slot: Arc<InFlight>,
+}
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        // a leader which panicked is reported to its waiters as a failed call
+        if let Ok(mut result) = self.slot.result.lock() {
+            if result.is_none() {
+                *result = Some(None);
+            }
+        }
+        self.slot.done.notify_all();
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// Suppresses duplicate concurrent calls. While a call is in flight, any
+/// identical call made from another thread waits for it and receives the
+/// same response instead of reaching the server a second time.
+///
+/// Calls are identical if they share an idempotency key or, lacking one,
+/// have the same body. A `Deduplicator` is shared between threads, each
+/// calling through its own `Client`.
+pub struct Deduplicator {
+    in_flight: Calls,
+}
+
+impl Deduplicator {
+    pub fn new() -> Deduplicator {
+        Deduplicator { in_flight: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// Performs `request` through `client`, or waits for an identical call
+    /// already in flight.
+    pub fn call(&self, client: &Client, request: &Request) -> Option<Response> {
+        self.call_with(request, || client.remote_call(request))
+    }
+
+    fn call_with<F>(&self, request: &Request, perform: F) -> Option<Response> where
+        F: FnOnce() -> Option<Response>,
+    {
+        let key = call_key(request);
+        let (slot, leader) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight.contains_key(&key) {
+                let slot = in_flight.get(&key).unwrap().clone();
+                slot.waiters.fetch_add(1, Ordering::SeqCst);
+                (slot, false)
+            } else {
+                let slot = Arc::new(InFlight {
+                    result: Mutex::new(None),
+                    done: Condvar::new(),
+                    waiters: AtomicUsize::new(0),
+                });
+                in_flight.insert(key.clone(), slot.clone());
+                (slot, true)
+            }
+        };
+
+        if !leader {
+            let mut result = slot.result.lock().unwrap();
+            while result.is_none() {
+                result = slot.done.wait(result).unwrap();
+            }
+            return result.clone().unwrap();
+        }
+
+        let completion = Completion { in_flight: self.in_flight.clone(), key: key, slot: slot };
+        let response = perform();
+        *completion.slot.result.lock().unwrap() = Some(response.clone());
+        response
+    }
+}
+
+fn call_key(request: &Request) -> string::String {
+    match request.idempotency_key {
+        Some(ref key) => format!("key:{}", key),
+        None => format!("body:{}", request.document()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deduplicator, call_key};
+    use protocol::Request;
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc::channel;
+    use std::thread::Thread;
+
+    #[test]
+    fn test_leader_panic_releases_waiters() {
+        let dedup = Arc::new(Deduplicator::new());
+        let request = Request::new("slow").finalize();
+        let (started_tx, started_rx) = channel();
+        let (fail_tx, fail_rx) = channel::<()>();
+
+        let leader = {
+            let (dedup, request) = (dedup.clone(), request.clone());
+            Thread::scoped(move || {
+                dedup.call_with(&request, || {
+                    started_tx.send(()).unwrap();
+                    fail_rx.recv().unwrap();
+                    panic!("leader failed")
+                })
+            })
+        };
+        started_rx.recv().unwrap();
+        let waiter = {
+            let (dedup, request) = (dedup.clone(), request.clone());
+            Thread::scoped(move || {
+                dedup.call_with(&request, || panic!("the waiter performed the call"))
+            })
+        };
+        // the leader holds its slot until told to fail, so the waiter joins it
+        loop {
+            let joined = dedup.in_flight.lock().unwrap().get(&call_key(&request))
+                .map(|slot| slot.waiters.load(Ordering::SeqCst) == 1);
+            if joined == Some(true) {
+                break;
+            }
+            Thread::yield_now();
+        }
+        fail_tx.send(()).unwrap();
+
+        assert!(leader.join().is_err());
+        assert_eq!(waiter.join().ok().unwrap(), None);
+        // the slot was released, so a later call is performed afresh
+        assert_eq!(dedup.call_with(&request, || None), None);
+        assert!(dedup.in_flight.lock().unwrap().is_empty());
+    }
+}
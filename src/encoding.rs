@@ -15,22 +15,32 @@ use self::ErrorCode::*;
 use self::ParserError::*;
 use self::DecoderError::*;
 
+use std::ascii::AsciiExt;
+use std::cell::RefCell;
 use std::collections::{HashMap, BTreeMap};
+use std::default::Default;
 use std::error::Error as StdError;
-use std::mem::{swap, transmute};
+use std::mem::{replace, swap, transmute};
 use std::num::{Float, Int};
 use std::ops::Index;
+use std::rc::Rc;
 use std::str::{FromStr};
 use std::string;
-use std::{char, f64, fmt, io, num, str};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{char, f64, fmt, i32, i64, io, num, str};
 use std;
 
 use rustc_serialize::{Encodable, Decodable};
+use rustc_serialize::base64::{FromBase64, ToBase64, STANDARD};
 use rustc_serialize::Encoder as SerializeEncoder;
 use rustc_serialize::Decoder as SerializeDecoder;
 
+use datetime::DateTime;
+use time;
 use xml;
 use xml::EventReader;
+use xml::reader::ParserConfig;
 use xml::reader::events;
 
 /// Represents an XML-RPC data value
@@ -43,15 +53,107 @@ pub enum Xml {
      Array(self::Array),
      Object(self::Object),
      Base64(Vec<u8>), // FIXME: added for xml-rpc, not in JSON
-     DateTime, // FIXME: need to implement
+     DateTime(DateTime),
      Null,
 }
 
+/// Name of the struct through which dateTime values pass the rustc-serialize
+/// encoder, holding their text in a single field. The XML-RPC encoder writes
+/// it as `<dateTime.iso8601>`.
+pub const DATETIME_STRUCT: &'static str = "__xmlrpc_dateTime.iso8601";
+
+/// Name of the struct through which base64 values pass the rustc-serialize
+/// encoder, holding their encoded text in a single field. The XML-RPC
+/// encoder writes it as `<base64>`.
+const BASE64_STRUCT: &'static str = "__xmlrpc_base64";
+
+const UTF8_BOM: &'static [u8] = &[0xEF, 0xBB, 0xBF];
+
 pub type Array = Vec<Xml>;
 pub type Object = BTreeMap<string::String, Xml>;
 
 pub struct AsXml<'a, T: 'a> { inner: &'a T }
 
+/// An array whose elements are drawn from an iterator as it is encoded;
+/// see `array_from_iter`.
+pub struct IterArray<I> {
+    iter: RefCell<Option<I>>,
+}
+
+/// Wraps `iter` so that it encodes as an `<array>` of its items, each
+/// written as it is produced. As the iterator is consumed, the value
+/// encodes its items only once; later encodings give an empty array.
+pub fn array_from_iter<I: Iterator>(iter: I) -> IterArray<I> {
+    IterArray { iter: RefCell::new(Some(iter)) }
+}
+
+impl<T: Encodable, I: Iterator<Item=T>> Encodable for IterArray<I> {
+    fn encode<S: SerializeEncoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        let iter = self.iter.borrow_mut().take();
+        s.emit_seq(0, |s| {
+            if let Some(iter) = iter {
+                for (i, item) in iter.enumerate() {
+                    try!(s.emit_seq_elt(i, |s| item.encode(s)));
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Struct members as an ordered list of pairs, for APIs where member order
+/// matters or names repeat. Encodes as a `<struct>` with the members in the
+/// order given, duplicates included. Decoding yields the members of a
+/// parsed struct, which are held sorted by name and with one value per name.
+#[derive(Clone, PartialEq, Show)]
+pub struct Members<T>(pub Vec<(string::String, T)>);
+
+impl<T: Encodable> Encodable for Members<T> {
+    fn encode<S: SerializeEncoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_map(self.0.len(), |s| {
+            for (i, &(ref name, ref value)) in self.0.iter().enumerate() {
+                try!(s.emit_map_elt_key(i, |s| name.encode(s)));
+                try!(s.emit_map_elt_val(i, |s| value.encode(s)));
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<T: Decodable> Decodable for Members<T> {
+    fn decode<D: SerializeDecoder>(d: &mut D) -> Result<Members<T>, D::Error> {
+        d.read_map(|d, len| {
+            let mut members = Vec::with_capacity(len);
+            for i in range(0, len) {
+                let name = try!(d.read_map_elt_key(i, |d| Decodable::decode(d)));
+                let value = try!(d.read_map_elt_val(i, |d| Decodable::decode(d)));
+                members.push((name, value));
+            }
+            Ok(Members(members))
+        })
+    }
+}
+
+/// Node counts and sizes of an XML value, as returned by `Xml::stats`.
+#[derive(Clone, Copy, Default, PartialEq, Show)]
+pub struct XmlStats {
+    pub ints: usize,
+    pub doubles: usize,
+    pub booleans: usize,
+    pub strings: usize,
+    pub datetimes: usize,
+    pub base64s: usize,
+    pub nils: usize,
+    pub arrays: usize,
+    pub structs: usize,
+    /// Nesting depth, 1 for a scalar
+    pub max_depth: usize,
+    /// Bytes of string values and member names
+    pub string_bytes: usize,
+    /// Decoded bytes of base64 values
+    pub base64_bytes: usize,
+}
+
 /// The errors that can arise while parsing an XML stream.
 #[derive(Clone, Copy, PartialEq)]
 pub enum ErrorCode {
@@ -60,6 +162,9 @@ pub enum ErrorCode {
     EOFWhileParsingArray,
     EOFWhileParsingValue,
     EOFWhileParsingString,
+    TrailingCharacters,
+    /// Arrays and structs nest deeper than `ParserOptions::max_depth`
+    NestingTooDeep,
 }
 
 #[derive(Clone, Copy, PartialEq, Show)]
@@ -72,15 +177,118 @@ pub enum ParserError {
 // Builder and Parser have the same errors.
 pub type BuilderError = ParserError;
 
+/// Options controlling how strictly an XML-RPC document is parsed.
+///
+/// The default is spec-pure; `ParserOptions::lenient()` accepts the common
+/// deviations emitted by real-world servers.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub struct ParserOptions {
+    /// Accept a leading '+' and scientific notation in `<int>` and `<double>`
+    pub lenient_numbers: bool,
+    /// Accept "true" and "false" (in any case) in `<boolean>`
+    pub lenient_booleans: bool,
+    /// Trim leading and trailing whitespace from numeric, boolean and
+    /// dateTime values (strings are never trimmed)
+    pub trim_scalars: bool,
+    /// Have xml-rs drop whitespace-only text entirely, including the
+    /// content of a `<string>` holding only whitespace
+    pub trim_whitespace: bool,
+    /// Read CDATA sections as text; otherwise they are a syntax error
+    pub cdata_to_characters: bool,
+    /// Accept the Apache XML-RPC extension types `ex:i8`, `ex:dateTime`
+    /// and `ex:serializable` (read as base64); `ex:nil` is always accepted
+    pub apache_extensions: bool,
+    /// Skip elements the parser does not know, with their content, rather
+    /// than failing on them
+    pub skip_unknown_elements: bool,
+    /// The deepest nesting of arrays and structs accepted, which keeps
+    /// hostile documents from exhausting the stack
+    pub max_depth: usize,
+}
+
+/// The nesting of arrays and structs accepted unless the caller chooses
+/// another limit.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+impl ParserOptions {
+    /// Options accepting all supported deviations from the specification.
+    pub fn lenient() -> ParserOptions {
+        ParserOptions {
+            lenient_numbers: true,
+            lenient_booleans: true,
+            trim_scalars: true,
+            trim_whitespace: false,
+            cdata_to_characters: true,
+            apache_extensions: false,
+            skip_unknown_elements: true,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Returns the xml-rs reader configuration for these options.
+    fn reader_config(&self) -> ParserConfig {
+        ParserConfig::new()
+            .trim_whitespace(self.trim_whitespace)
+            .cdata_to_characters(self.cdata_to_characters)
+    }
+}
+
+impl Default for ParserOptions {
+    fn default() -> ParserOptions {
+        ParserOptions {
+            lenient_numbers: false,
+            lenient_booleans: false,
+            trim_scalars: false,
+            trim_whitespace: false,
+            cdata_to_characters: true,
+            apache_extensions: false,
+            skip_unknown_elements: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Show)]
 pub enum DecoderError {
     ParseError(ParserError),
     ExpectedError(string::String, string::String),
     MissingFieldError(string::String),
     UnknownVariantError(string::String),
+    UnknownMemberError(string::String),
+    /// path to the offending value, e.g. `value[0].nodes[3].pid`, and the
+    /// error raised there
+    PathError(string::String, Box<DecoderError>),
     ApplicationError(string::String)
 }
 
+/// A deviation from the specification accepted by lenient parsing or
+/// decoding, recorded so that interoperability quirks can be logged.
+#[derive(Clone, PartialEq, Show)]
+pub enum Warning {
+    /// A number with a leading '+' or in scientific notation
+    LenientNumber(string::String),
+    /// A boolean written as "true" or "false"
+    LenientBoolean(string::String),
+    /// A scalar whose surrounding whitespace was trimmed
+    TrimmedScalar(string::String),
+    /// An unknown element, skipped with its content
+    SkippedElement(string::String),
+    /// A value converted between types while decoding: the path to the
+    /// value, its type and the type it was decoded as
+    Coerced(string::String, &'static str, &'static str),
+}
+
+/// The errors that can arise while encoding a value.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum EncoderError {
+    /// A map key or dateTime text was not a scalar value
+    NotScalar,
+    /// The underlying writer failed
+    WriteError,
+    /// A method name contains characters its `MethodNames` policy forbids
+    InvalidMethodName,
+}
+
 /// Returns a readable error string for a given error code.
 pub fn error_str(error: ErrorCode) -> &'static str {
     match error {
@@ -89,30 +297,87 @@ pub fn error_str(error: ErrorCode) -> &'static str {
         EOFWhileParsingArray => "EOF While parsing array",
         EOFWhileParsingValue => "EOF While parsing value",
         EOFWhileParsingString => "EOF While parsing string",
+        TrailingCharacters => "trailing characters",
+        NestingTooDeep => "arrays and structs nested too deeply",
     }
 }
 
 /// Shortcut function to decode a XML `&str` into an object
 pub fn decode<T: Decodable>(s: &str) -> DecodeResult<T> {
-    let xml = match Xml::from_str(s) {
+    decode_with(s, Default::default(), Default::default())
+}
+
+/// Shortcut function to decode a XML `&str` into an object using the
+/// specified parser and decoder options
+pub fn decode_with<T: Decodable>(s: &str,
+                                 parser_options: ParserOptions,
+                                 decoder_options: DecoderOptions) -> DecodeResult<T> {
+    let xml = match Xml::from_str_with(s, parser_options) {
         Ok(x) => x,
         Err(e) => return Err(ParseError(e))
     };
 
-    let mut decoder = Decoder::new(xml);
+    let mut decoder = Decoder::new_with(xml, decoder_options);
+    Decodable::decode(&mut decoder)
+}
+
+/// Parses a `<methodCall>` document into its method name and parameters
+pub fn parse_method_call(s: &str, options: ParserOptions)
+                         -> Result<(string::String, Vec<Xml>), BuilderError> {
+    let mut builder = Builder::new_with(io::BufReader::new(s.as_bytes()), options);
+    builder.build_method_call()
+}
+
+/// Parses a `<methodResponse>` document into its parameters, or the value
+/// of its fault
+pub fn parse_method_response(s: &str, options: ParserOptions)
+                             -> Result<Result<Vec<Xml>, Xml>, BuilderError> {
+    let mut builder = Builder::new_with(io::BufReader::new(s.as_bytes()), options);
+    builder.build_method_response()
+}
+
+/// Shortcut function to decode an already parsed XML value into an object
+pub fn from_value<T: Decodable>(xml: &Xml) -> DecodeResult<T> {
+    let mut decoder = Decoder::new(xml.clone());
     Decodable::decode(&mut decoder)
 }
 
 /// Shortcut function to encode a `T` into an XML `String`
 pub fn encode<T: Encodable>(object: &T) -> string::String {
+    encode_with(object, Default::default())
+}
+
+/// Shortcut function to encode a `T` into an XML `String` using the
+/// specified encoder options
+pub fn encode_with<T: Encodable>(object: &T, options: EncoderOptions) -> string::String {
     let mut s = String::new();
     {
-        let mut encoder = Encoder::new(&mut s);
+        let mut encoder = Encoder::new_with(&mut s, options);
         let _ = object.encode(&mut encoder);
     }
     s
 }
 
+/// Encodes a `T` into an XML `String`, failing rather than returning
+/// partial output if the value cannot be represented
+pub fn try_encode<T: Encodable>(object: &T) -> Result<string::String, EncoderError> {
+    try_encode_with(object, Default::default())
+}
+
+/// Encodes a `T` into an XML `String` using the specified encoder options,
+/// failing rather than returning partial output
+pub fn try_encode_with<T: Encodable>(object: &T, options: EncoderOptions)
+                                     -> Result<string::String, EncoderError> {
+    let mut s = String::new();
+    {
+        let mut encoder = Encoder::new_with(&mut s, options);
+        if object.encode(&mut encoder).is_err() {
+            return Err(encoder.error.unwrap_or(EncoderError::WriteError));
+        }
+    }
+    Ok(s)
+}
+
 impl fmt::Show for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         error_str(*self).fmt(f)
@@ -129,11 +394,34 @@ impl StdError for DecoderError {
     fn cause(&self) -> Option<&StdError> {
         match *self {
             DecoderError::ParseError(ref e) => Some(e as &StdError),
+            DecoderError::PathError(_, ref e) => Some(&**e as &StdError),
             _ => None,
         }
     }
 }
 
+impl fmt::String for DecoderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExpectedError(ref expected, ref found) => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            PathError(ref path, ref e) => write!(f, "{} at {}", e, path),
+            ref e => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl StdError for EncoderError {
+    fn description(&self) -> &str {
+        match *self {
+            EncoderError::NotScalar => "map key or dateTime is not a scalar",
+            EncoderError::WriteError => "failed to write xml",
+            EncoderError::InvalidMethodName => "invalid method name",
+        }
+    }
+}
+
 impl StdError for ParserError {
     fn description(&self) -> &str { "failed to parse xml" }
     fn detail(&self) -> Option<std::string::String> { Some(format!("{:?}", self)) }
@@ -142,66 +430,356 @@ impl StdError for ParserError {
 pub type EncodeResult = fmt::Result;
 pub type DecodeResult<T> = Result<T, DecoderError>;
 
-fn escape_str(wr: &mut fmt::Writer, v: &str) -> fmt::Result {
-    wr.write_str(xml::escape::escape_str(v).as_slice())
+/// Writes `v` with the characters XML reserves escaped and, if the options
+/// ask for an ASCII document, non-ASCII characters as character references.
+pub fn escape_str(wr: &mut fmt::Writer, v: &str, options: &EncoderOptions) -> fmt::Result {
+    let ascii_only = options.ascii_only;
+    let bytes = v.as_bytes();
+    let mut start = 0;
+    // every character needing an escape starts with a byte found in no
+    // other character, so the runs between them are found by scanning bytes
+    while let Some(offset) = bytes[start..].iter().position(|&b| needs_escape(b, ascii_only)) {
+        let i = start + offset;
+        try!(wr.write_str(&v[start..i]));
+        let reference;
+        let (escaped, len) = match bytes[i] {
+            b'&' => ("&amp;", 1),
+            b'<' => ("&lt;", 1),
+            b'>' => ("&gt;", 1),
+            b'"' => ("&quot;", 1),
+            b'\'' => ("&apos;", 1),
+            _ => {
+                let c = v.char_at(i);
+                reference = format!("&#x{:X};", c as u32);
+                (reference.as_slice(), c.len_utf8())
+            }
+        };
+        try!(wr.write_str(escaped));
+        start = i + len;
+    }
+    wr.write_str(&v[start..])
+}
+
+fn needs_escape(b: u8, ascii_only: bool) -> bool {
+    match b {
+        b'&' | b'<' | b'>' | b'"' | b'\'' => true,
+        _ => ascii_only && b >= 0x80,
+    }
 }
 
-fn escape_char(writer: &mut fmt::Writer, v: char) -> fmt::Result {
+fn escape_char(writer: &mut fmt::Writer, v: char, options: &EncoderOptions) -> fmt::Result {
     let mut buf = [0; 4];
     let n = v.encode_utf8(&mut buf).unwrap();
     let buf = unsafe { str::from_utf8_unchecked(&buf[0..n]) };
-    escape_str(writer, buf)
+    escape_str(writer, buf, options)
+}
+
+/// Options controlling the output of an `Encoder`.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub struct EncoderOptions {
+    /// Escape all non-ASCII characters as `&#xNNNN;` so that the output
+    /// document is pure ASCII
+    pub ascii_only: bool,
+    /// Omit struct members whose value is `None` rather than writing
+    /// `<nil/>`, which is an extension to the spec
+    pub skip_nil: bool,
+    /// How to write integers outside the range of `<int>`
+    pub large_integers: LargeIntegers,
+    /// The element written around 32-bit integers
+    pub int_tag: IntTag,
+    /// Write nil as Apache's `<ex:nil/>` and integers beyond `<int>` as
+    /// `<ex:i8>`, overriding `large_integers`
+    pub apache_extensions: bool,
+    /// Write a tuple struct with a single field as that field's value
+    /// rather than as a struct with a `_field0` member. Off by default, as
+    /// it changes the format peers and stored documents expect.
+    pub transparent_newtypes: bool,
+    /// How to write `()` and unit structs
+    pub units: Units,
+}
+
+/// The name derived encodings give the field of a tuple struct with one field
+const NEWTYPE_FIELD: &'static str = "_field0";
+
+/// The namespace of the Apache XML-RPC extension types
+pub const APACHE_EXTENSIONS_NS: &'static str = "http://ws.apache.org/xmlrpc/namespaces/extensions";
+
+/// A set of parser and encoder options suiting a family of servers.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum Profile {
+    /// The specification, with `<nil/>` as its one extension
+    Spec,
+    /// Apache XML-RPC with its extension types enabled
+    Apache,
+    /// Python's xmlrpclib and xmlrpc.client, whose doubles may be written
+    /// in scientific notation. None is marshalled as `<nil/>` when the
+    /// Python side sets allow_none.
+    Python,
+}
+
+impl Profile {
+    pub fn parser_options(&self) -> ParserOptions {
+        match *self {
+            Profile::Spec => Default::default(),
+            Profile::Apache => ParserOptions { apache_extensions: true, ..Default::default() },
+            Profile::Python => ParserOptions { lenient_numbers: true, ..Default::default() },
+        }
+    }
+
+    pub fn encoder_options(&self) -> EncoderOptions {
+        match *self {
+            Profile::Spec => Default::default(),
+            Profile::Apache => EncoderOptions { apache_extensions: true, ..Default::default() },
+            Profile::Python => Default::default(),
+        }
+    }
+}
+
+/// The element name used for 32-bit integers, which the spec allows to be
+/// either `<int>` or `<i4>`.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum IntTag {
+    Int,
+    I4,
+}
+
+impl IntTag {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            IntTag::Int => "int",
+            IntTag::I4 => "i4",
+        }
+    }
+}
+
+/// Policy for integers that do not fit the 32 bits of an XML-RPC `<int>`.
+/// The decoder always accepts the string form back into integer types, and
+/// the double form when its own `large_integers` is `Double`.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum LargeIntegers {
+    /// Keep the low 32 bits, as a cast to i32 would
+    Truncate,
+    /// Write the exact value as a `<string>`
+    String,
+    /// Write the value as a `<double>`, losing precision beyond 2^53; see
+    /// `Encoder::lost_precision`
+    Double,
+}
+
+impl Default for EncoderOptions {
+    fn default() -> EncoderOptions {
+        EncoderOptions {
+            ascii_only: false,
+            skip_nil: false,
+            large_integers: LargeIntegers::Truncate,
+            int_tag: IntTag::Int,
+            apache_extensions: false,
+            transparent_newtypes: false,
+            units: Units::Nil,
+        }
+    }
+}
+
+/// How the encoder writes `()` and unit structs, which carry no data. The
+/// decoder accepts any of the three forms back into either.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum Units {
+    /// `<nil/>`, an extension to the spec
+    Nil,
+    /// `<struct></struct>`
+    EmptyStruct,
+    /// `<string></string>`, for servers without nil
+    EmptyString,
 }
 
 /// A structure for implementing serialization to XML-RPC.
 pub struct Encoder<'a> {
     writer: &'a mut (fmt::Writer+'a),
+    options: EncoderOptions,
+    // write scalars without their type tags, as in map keys and dateTime values
+    emit_bare: bool,
+    // with skip_nil, the struct member whose header is held back until its
+    // value turns out not to be None
+    pending_member: Option<string::String>,
+    skipped_nil: bool,
+    lost_precision: bool,
+    error: Option<EncoderError>,
+    // for each struct being written, whether its `<struct>` tag is held back
+    // until its first field shows whether it is a newtype
+    held_structs: Vec<bool>,
 }
 
 impl<'a> Encoder<'a> {
     /// Creates a new XML-RPC encoder whose output will be written to the writer
     /// specified.
     pub fn new(writer: &'a mut fmt::Writer) -> Encoder<'a> {
-        Encoder { writer: writer }
+        Encoder::new_with(writer, Default::default())
+    }
+
+    /// Creates a new XML-RPC encoder with the specified options whose output
+    /// will be written to the writer specified.
+    pub fn new_with(writer: &'a mut fmt::Writer, options: EncoderOptions) -> Encoder<'a> {
+        Encoder {
+            writer: writer,
+            options: options,
+            emit_bare: false,
+            pending_member: None,
+            skipped_nil: false,
+            lost_precision: false,
+            error: None,
+            held_structs: Vec::new(),
+        }
+    }
+
+    fn write_nil(&mut self) -> EncodeResult {
+        if self.emit_bare { return self.not_scalar(); }
+        try!(self.write_pending_member());
+        if self.options.apache_extensions {
+            return write!(self.writer, "<ex:nil xmlns:ex=\"{}\"/>", APACHE_EXTENSIONS_NS);
+        }
+        write!(self.writer, "<nil/>")
+    }
+
+    /// Writes `()` or a unit struct as the options direct.
+    fn emit_unit(&mut self) -> EncodeResult {
+        match self.options.units {
+            Units::Nil => self.write_nil(),
+            Units::EmptyStruct => {
+                if self.emit_bare { return self.not_scalar(); }
+                try!(self.write_pending_member());
+                write!(self.writer, "<struct></struct>")
+            }
+            Units::EmptyString => self.emit_str(""),
+        }
+    }
+
+    fn not_scalar(&mut self) -> EncodeResult {
+        self.error = Some(EncoderError::NotScalar);
+        Err(fmt::Error)
+    }
+
+    /// Returns true if some integer was written as a `<double>` which
+    /// cannot represent it exactly.
+    pub fn lost_precision(&self) -> bool {
+        self.lost_precision
+    }
+
+    /// Writes an `<array>` of the items of `iter`, each written as it is
+    /// produced, so that no collection of the items is ever held.
+    pub fn emit_array_from_iter<I, T>(&mut self, iter: I) -> EncodeResult where
+        I: Iterator<Item=T>,
+        T: Encodable,
+    {
+        self.emit_seq(iter.size_hint().0, |e| {
+            for (i, item) in iter.enumerate() {
+                try!(e.emit_seq_elt(i, |e| item.encode(e)));
+            }
+            Ok(())
+        })
+    }
+
+    fn emit_unsigned(&mut self, v: u64) -> EncodeResult {
+        if v <= i32::MAX as u64 {
+            return self.emit_i32(v as i32);
+        }
+        self.emit_large(v as i32, v.to_string(), v as f64, v > 1 << 53, v <= i64::MAX as u64)
+    }
+
+    fn emit_signed(&mut self, v: i64) -> EncodeResult {
+        if v as i32 as i64 == v {
+            return self.emit_i32(v as i32);
+        }
+        self.emit_large(v as i32, v.to_string(), v as f64, v > 1 << 53 || v < -(1 << 53), true)
+    }
+
+    /// Writes an integer outside the range of `<int>` as the options direct.
+    /// Values beyond i64, which `<ex:i8>` cannot carry, always follow
+    /// `large_integers`.
+    fn emit_large(&mut self, truncated: i32, exact: string::String,
+                  approx: f64, inexact: bool, fits_i8: bool) -> EncodeResult {
+        if self.options.apache_extensions && fits_i8 && !self.emit_bare {
+            try!(self.write_pending_member());
+            return write!(self.writer, "<ex:i8 xmlns:ex=\"{}\">{}</ex:i8>",
+                          APACHE_EXTENSIONS_NS, exact);
+        }
+        match self.options.large_integers {
+            LargeIntegers::Truncate => self.emit_i32(truncated),
+            LargeIntegers::String => self.emit_str(exact.as_slice()),
+            LargeIntegers::Double => {
+                self.lost_precision = self.lost_precision || inexact;
+                try!(self.write_pending_member());
+                if self.emit_bare { return write!(self.writer, "{}", exact); }
+                write!(self.writer, "<double>{}</double>", approx)
+            }
+        }
+    }
+
+    fn write_pending_member(&mut self) -> EncodeResult {
+        match self.pending_member.take() {
+            Some(name) => self.write_member_start(name.as_slice()),
+            None => Ok(()),
+        }
+    }
+
+    fn write_member_start(&mut self, name: &str) -> EncodeResult {
+        try!(write!(self.writer, "<member><name>"));
+        try!(escape_str(self.writer, name, &self.options));
+        write!(self.writer, "</name><value>")
     }
 }
 
 impl<'a> SerializeEncoder for Encoder<'a> {
     type Error = fmt::Error;
-    fn emit_nil(&mut self) -> EncodeResult { write!(self.writer, "<nil/>") }
+    fn emit_nil(&mut self) -> EncodeResult {
+        self.emit_unit()
+    }
 
-    fn emit_usize(&mut self, v: usize) -> EncodeResult { self.emit_i32(v as i32) }
-    fn emit_u64(&mut self, v: u64) -> EncodeResult { self.emit_i32(v as i32) }
-    fn emit_u32(&mut self, v: u32) -> EncodeResult { self.emit_i32(v as i32) }
+    fn emit_usize(&mut self, v: usize) -> EncodeResult { self.emit_unsigned(v as u64) }
+    fn emit_u64(&mut self, v: u64) -> EncodeResult { self.emit_unsigned(v) }
+    fn emit_u32(&mut self, v: u32) -> EncodeResult { self.emit_unsigned(v as u64) }
     fn emit_u16(&mut self, v: u16) -> EncodeResult { self.emit_i32(v as i32) }
     fn emit_u8(&mut self, v: u8) -> EncodeResult { self.emit_i32(v as i32) }
 
-    fn emit_isize(&mut self, v: isize) -> EncodeResult { self.emit_i32(v as i32) }
-    fn emit_i64(&mut self, v: i64) -> EncodeResult { self.emit_i32(v as i32) }
+    fn emit_isize(&mut self, v: isize) -> EncodeResult { self.emit_signed(v as i64) }
+    fn emit_i64(&mut self, v: i64) -> EncodeResult { self.emit_signed(v) }
     fn emit_i32(&mut self, v: i32) -> EncodeResult { // XML-RPC only supports 4-byte signed integer
-        // FIXME, precondition numbers to check range
-        write!(self.writer, "<int>{}</int>", v)
+        try!(self.write_pending_member());
+        if self.emit_bare { return write!(self.writer, "{}", v); }
+        let tag = self.options.int_tag.as_str();
+        write!(self.writer, "<{}>{}</{}>", tag, v, tag)
     }
     fn emit_i16(&mut self, v: i16) -> EncodeResult { self.emit_i32(v as i32) }
     fn emit_i8(&mut self, v: i8) -> EncodeResult { self.emit_i32(v as i32) }
 
     fn emit_bool(&mut self, v: bool) -> EncodeResult {
+        try!(self.write_pending_member());
+        if self.emit_bare { return write!(self.writer, "{}", v); }
         write!(self.writer, "<boolean>{}</boolean>", v as u8)
     }
 
     fn emit_f64(&mut self, v: f64) -> EncodeResult {
+        try!(self.write_pending_member());
+        if self.emit_bare { return write!(self.writer, "{}", v); }
         write!(self.writer, "<double>{}</double>", v)
     }
     fn emit_f32(&mut self, v: f32) -> EncodeResult { self.emit_f64(v as f64) }
 
     fn emit_char(&mut self, v: char) -> EncodeResult {
+        try!(self.write_pending_member());
+        if self.emit_bare {
+            return escape_char(self.writer, v, &self.options);
+        }
         try!(write!(self.writer, "<string>"));
-        try!(escape_char(self.writer, v));
+        try!(escape_char(self.writer, v, &self.options));
         write!(self.writer, "</string>")
     }
     fn emit_str(&mut self, v: &str) -> EncodeResult {
+        try!(self.write_pending_member());
+        if self.emit_bare {
+            return escape_str(self.writer, v, &self.options);
+        }
         try!(write!(self.writer, "<string>"));
-	try!(escape_str(self.writer, v));
+	try!(escape_str(self.writer, v, &self.options));
         write!(self.writer, "</string>")
     }
 
@@ -280,20 +858,63 @@ impl<'a> SerializeEncoder for Encoder<'a> {
         self.emit_enum_variant_arg(idx, f)
     }
 
-    fn emit_struct<F>(&mut self, _: &str, _: usize, f: F) -> EncodeResult where
+    fn emit_struct<F>(&mut self, name: &str, len: usize, f: F) -> EncodeResult where
         F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
     {
-        try!(write!(self.writer, "<struct>"));
-        try!(f(self));
-        write!(self.writer, "</struct>")
+        if self.emit_bare { return self.not_scalar(); }
+        if len == 0 {
+            return self.emit_unit();
+        }
+        try!(self.write_pending_member());
+        if name == DATETIME_STRUCT {
+            try!(write!(self.writer, "<dateTime.iso8601>"));
+            self.emit_bare = true;
+            let result = f(self);
+            self.emit_bare = false;
+            try!(result);
+            return write!(self.writer, "</dateTime.iso8601>");
+        }
+        if name == BASE64_STRUCT {
+            try!(write!(self.writer, "<base64>"));
+            self.emit_bare = true;
+            let result = f(self);
+            self.emit_bare = false;
+            try!(result);
+            return write!(self.writer, "</base64>");
+        }
+        let held = len == 1 && self.options.transparent_newtypes;
+        if !held {
+            try!(write!(self.writer, "<struct>"));
+        }
+        self.held_structs.push(held);
+        let result = f(self);
+        let newtype = self.held_structs.pop().unwrap();
+        try!(result);
+        if newtype { Ok(()) } else { write!(self.writer, "</struct>") }
     }
 
     fn emit_struct_field<F>(&mut self, name: &str, idx: usize, f: F) -> EncodeResult where
         F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
     {
-        try!(write!(self.writer, "<member>"));
-        try!(write!(self.writer, "<name>{}</name>", name)); // FIXME: encode str?
-        try!(write!(self.writer, "<value>"));
+        if self.emit_bare { return f(self); } // the text of a dateTime
+        if self.held_structs.last() == Some(&true) {
+            if name == NEWTYPE_FIELD {
+                return f(self);
+            }
+            *self.held_structs.last_mut().unwrap() = false;
+            try!(write!(self.writer, "<struct>"));
+        }
+        if self.options.skip_nil {
+            self.pending_member = Some(name.to_string());
+            try!(f(self));
+            if self.skipped_nil {
+                self.skipped_nil = false;
+                return Ok(());
+            }
+            try!(self.write_pending_member());
+            return write!(self.writer, "</value></member>");
+        }
+        try!(self.write_member_start(name));
         try!(f(self));
         try!(write!(self.writer, "</value>"));
         write!(self.writer, "</member>")
@@ -326,7 +947,13 @@ impl<'a> SerializeEncoder for Encoder<'a> {
     {
         f(self)
     }
-    fn emit_option_none(&mut self) -> EncodeResult { self.emit_nil() }
+    fn emit_option_none(&mut self) -> EncodeResult {
+        if self.pending_member.take().is_some() {
+            self.skipped_nil = true;
+            return Ok(());
+        }
+        self.write_nil()
+    }
     fn emit_option_some<F>(&mut self, f: F) -> EncodeResult where
         F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
     {
@@ -336,6 +963,8 @@ impl<'a> SerializeEncoder for Encoder<'a> {
     fn emit_seq<F>(&mut self, _len: usize, f: F) -> EncodeResult where
         F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
     {
+        if self.emit_bare { return self.not_scalar(); }
+        try!(self.write_pending_member());
         try!(write!(self.writer, "<array><data>"));
         try!(f(self));
         write!(self.writer, "</data></array>")
@@ -352,41 +981,126 @@ impl<'a> SerializeEncoder for Encoder<'a> {
     fn emit_map<F>(&mut self, _len: usize, f: F) -> EncodeResult where
         F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
     {
-        Ok(())
-        // FIXME: this is JSON source
-        //try!(write!(self.writer, "{{"));
-        //try!(f(self));
-        //write!(self.writer, "}}")
+        // maps are encoded as structs, with keys written as member names
+        if self.emit_bare { return self.not_scalar(); }
+        try!(self.write_pending_member());
+        try!(write!(self.writer, "<struct>"));
+        try!(f(self));
+        write!(self.writer, "</struct>")
     }
 
-    //fn emit_map_elt_key<F>(&mut self, idx: usize, mut f: F) -> EncodeResult where
-    // FIXME: implement
-    fn emit_map_elt_key<F>(&mut self, idx: usize, f: F) -> EncodeResult where
+    fn emit_map_elt_key<F>(&mut self, _idx: usize, mut f: F) -> EncodeResult where
         F: FnMut(&mut Encoder<'a>) -> EncodeResult,
     {
-        //if idx != 0 { try!(write!(self.writer, ",")) }
-        //// ref #12967, make sure to wrap a key in double quotes,
-        //// in the event that its of a type that omits them (eg numbers)
-        //let mut buf = Vec::new();
-        // // FIXME(14302) remove the transmute and unsafe block.
-        //unsafe {
-        //    let mut check_encoder = Encoder::new(&mut buf);
-        //    try!(f(transmute(&mut check_encoder)));
-        //}
-        //let out = str::from_utf8(buf[]).unwrap();
-        //let needs_wrapping = out.char_at(0) != '"' && out.char_at_reverse(out.len()) != '"';
-        //if needs_wrapping { try!(write!(self.writer, "\"")); }
-        //try!(f(self));
-        //if needs_wrapping { try!(write!(self.writer, "\"")); }
-        Ok(())
+        try!(write!(self.writer, "<member><name>"));
+        self.emit_bare = true;
+        let result = f(self);
+        self.emit_bare = false;
+        try!(result);
+        write!(self.writer, "</name>")
     }
 
     fn emit_map_elt_val<F>(&mut self, _idx: usize, f: F) -> EncodeResult where
         F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
     {
-        Ok(())
-        //try!(write!(self.writer, ":"));
-        //f(self)
+        try!(write!(self.writer, "<value>"));
+        try!(f(self));
+        write!(self.writer, "</value></member>")
+    }
+}
+
+/// Writes XML-RPC values piece by piece, for payloads written by hand
+/// rather than through `Encodable`. Names and strings are escaped as the
+/// encoder escapes them; the caller is responsible for balancing each
+/// `begin_struct` and `begin_array` with its end.
+pub struct ValueWriter<'a> {
+    writer: &'a mut (fmt::Writer+'a),
+    options: EncoderOptions,
+}
+
+impl<'a> ValueWriter<'a> {
+    pub fn new(writer: &'a mut fmt::Writer) -> ValueWriter<'a> {
+        ValueWriter::new_with(writer, Default::default())
+    }
+
+    pub fn new_with(writer: &'a mut fmt::Writer, options: EncoderOptions) -> ValueWriter<'a> {
+        ValueWriter { writer: writer, options: options }
+    }
+
+    pub fn begin_struct(&mut self) -> EncodeResult {
+        write!(self.writer, "<struct>")
+    }
+
+    /// Writes a member named `name`, whose value `f` writes.
+    pub fn member<F>(&mut self, name: &str, f: F) -> EncodeResult where
+        F: FnOnce(&mut ValueWriter<'a>) -> EncodeResult,
+    {
+        try!(write!(self.writer, "<member><name>"));
+        try!(escape_str(self.writer, name, &self.options));
+        try!(write!(self.writer, "</name><value>"));
+        try!(f(self));
+        write!(self.writer, "</value></member>")
+    }
+
+    pub fn end_struct(&mut self) -> EncodeResult {
+        write!(self.writer, "</struct>")
+    }
+
+    pub fn begin_array(&mut self) -> EncodeResult {
+        write!(self.writer, "<array><data>")
+    }
+
+    /// Writes an array element, whose value `f` writes.
+    pub fn element<F>(&mut self, f: F) -> EncodeResult where
+        F: FnOnce(&mut ValueWriter<'a>) -> EncodeResult,
+    {
+        try!(write!(self.writer, "<value>"));
+        try!(f(self));
+        write!(self.writer, "</value>")
+    }
+
+    pub fn end_array(&mut self) -> EncodeResult {
+        write!(self.writer, "</data></array>")
+    }
+
+    pub fn int(&mut self, v: i32) -> EncodeResult {
+        let tag = self.options.int_tag.as_str();
+        write!(self.writer, "<{}>{}</{}>", tag, v, tag)
+    }
+
+    pub fn double(&mut self, v: f64) -> EncodeResult {
+        write!(self.writer, "<double>{}</double>", v)
+    }
+
+    pub fn boolean(&mut self, v: bool) -> EncodeResult {
+        write!(self.writer, "<boolean>{}</boolean>", v as u8)
+    }
+
+    pub fn string(&mut self, v: &str) -> EncodeResult {
+        try!(write!(self.writer, "<string>"));
+        try!(escape_str(self.writer, v, &self.options));
+        write!(self.writer, "</string>")
+    }
+
+    pub fn datetime(&mut self, v: &DateTime) -> EncodeResult {
+        write!(self.writer, "<dateTime.iso8601>{}</dateTime.iso8601>", v)
+    }
+
+    pub fn base64(&mut self, v: &[u8]) -> EncodeResult {
+        write!(self.writer, "<base64>{}</base64>", v.to_base64(STANDARD))
+    }
+
+    pub fn nil(&mut self) -> EncodeResult {
+        if self.options.apache_extensions {
+            return write!(self.writer, "<ex:nil xmlns:ex=\"{}\"/>", APACHE_EXTENSIONS_NS);
+        }
+        write!(self.writer, "<nil/>")
+    }
+
+    /// Writes any encodable value, as `encode_with` would.
+    pub fn value<T: Encodable>(&mut self, v: &T) -> EncodeResult {
+        let mut encoder = Encoder::new_with(&mut *self.writer, self.options);
+        v.encode(&mut encoder)
     }
 }
 
@@ -400,8 +1114,11 @@ impl Encodable for Xml {
             Xml::Array(ref v) => v.encode(e),
             Xml::Object(ref v) => v.encode(e), // FIXME: had to add hardcoded
                                                // impl for BTreeMap
-            Xml::Null => e.emit_nil(),
-            _ => Ok(()), // FIXME: add other types
+            Xml::DateTime(ref v) => v.encode(e),
+            Xml::Base64(ref v) => e.emit_struct(BASE64_STRUCT, 1, |e| {
+                e.emit_struct_field("base64", 0, |e| v.to_base64(STANDARD).encode(e))
+            }),
+            Xml::Null => e.emit_option(|e| e.emit_option_none()),
         }
     }
 }
@@ -416,21 +1133,49 @@ pub fn as_xml<T: Encodable>(t: &T) -> AsXml<T> {
 impl Xml {
 
     pub fn from_str(s: &str) -> Result<Self, BuilderError> {
-        //let mut builder = Builder::new(s.chars());
-        //builder.build()
-        let rdr = io::MemReader::new(String::from_str(s).into_bytes());
-        let brdr = io::BufferedReader::new(rdr);
-        let mut builder = Builder::new(brdr);
+        Xml::from_str_with(s, Default::default())
+    }
+
+    /// Parses an XML value from a string using the specified parser options.
+    pub fn from_str_with(s: &str, options: ParserOptions) -> Result<Self, BuilderError> {
+        Xml::from_bytes_with(s.as_bytes(), options)
+    }
+
+    /// Parses an XML value from a UTF-8 byte buffer, skipping a leading
+    /// byte order mark.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BuilderError> {
+        Xml::from_bytes_with(bytes, Default::default())
+    }
+
+    /// Parses an XML value from a UTF-8 byte buffer using the specified
+    /// parser options. Malformed or hostile input yields an error, never a
+    /// panic.
+    pub fn from_bytes_with(bytes: &[u8], options: ParserOptions) -> Result<Self, BuilderError> {
+        let bytes = if bytes.starts_with(UTF8_BOM) { &bytes[UTF8_BOM.len()..] } else { bytes };
+        let mut builder = Builder::new_with(io::BufReader::new(bytes), options);
         builder.build()
     }
 
     // FIXME: this should give us a method to build objects from an existing xml parser
     // such as for interpreting xml requests
     pub fn from_parser<B: Buffer>(p: xml::EventReader<B>) -> Result<Self, BuilderError> {
-        let mut builder = Builder { parser: p, token: None };
+        let mut builder = Builder {
+            parser: p,
+            token: None,
+            options: Default::default(),
+            warnings: RefCell::new(Vec::new()),
+            pending: None,
+            depth: 0,
+        };
         builder.build()
     }
 
+    /// Decodes this value, typically a subtree found with `find_path`, into
+    /// a rust value.
+    pub fn decode_into<T: Decodable>(&self) -> DecodeResult<T> {
+        from_value(self)
+    }
+
     /// If the XML value is an Object, returns the value associated with the provided key.
     /// Otherwise, returns None.
     pub fn find<'a>(&'a self, key: &str) -> Option<&'a Xml>{
@@ -477,6 +1222,78 @@ impl Xml {
         }
     }
 
+    /// Returns a copy of the value in which members named by any of `keys`,
+    /// at any depth, have their value replaced by "***", for logging
+    /// values which may carry passwords or tokens.
+    pub fn redact(&self, keys: &[&str]) -> Xml {
+        self.redact_with(&|name: &str| keys.contains(&name))
+    }
+
+    /// Returns a copy of the value in which members whose name satisfies
+    /// `is_secret`, at any depth, have their value replaced by "***".
+    pub fn redact_with(&self, is_secret: &Fn(&str) -> bool) -> Xml {
+        match *self {
+            Xml::Object(ref map) => {
+                let mut redacted = BTreeMap::new();
+                for (key, value) in map.iter() {
+                    let value = if (*is_secret)(key.as_slice()) {
+                        Xml::String("***".to_string())
+                    } else {
+                        value.redact_with(is_secret)
+                    };
+                    redacted.insert(key.clone(), value);
+                }
+                Xml::Object(redacted)
+            }
+            Xml::Array(ref array) => {
+                Xml::Array(array.iter().map(|value| value.redact_with(is_secret)).collect())
+            }
+            ref value => value.clone(),
+        }
+    }
+
+    /// Counts the nodes of the value by type and measures its depth and
+    /// payload size.
+    pub fn stats(&self) -> XmlStats {
+        let mut stats = XmlStats::default();
+        self.add_stats(&mut stats, 1);
+        stats
+    }
+
+    fn add_stats(&self, stats: &mut XmlStats, depth: usize) {
+        if depth > stats.max_depth {
+            stats.max_depth = depth;
+        }
+        match *self {
+            Xml::I32(_) => stats.ints += 1,
+            Xml::F64(_) => stats.doubles += 1,
+            Xml::Boolean(_) => stats.booleans += 1,
+            Xml::DateTime(_) => stats.datetimes += 1,
+            Xml::Null => stats.nils += 1,
+            Xml::String(ref s) => {
+                stats.strings += 1;
+                stats.string_bytes += s.len();
+            }
+            Xml::Base64(ref v) => {
+                stats.base64s += 1;
+                stats.base64_bytes += v.len();
+            }
+            Xml::Array(ref array) => {
+                stats.arrays += 1;
+                for value in array.iter() {
+                    value.add_stats(stats, depth + 1);
+                }
+            }
+            Xml::Object(ref map) => {
+                stats.structs += 1;
+                for (key, value) in map.iter() {
+                    stats.string_bytes += key.len();
+                    value.add_stats(stats, depth + 1);
+                }
+            }
+        }
+    }
+
     /// Returns true if the XML value is an Object. Returns false otherwise.
     pub fn is_object<'a>(&'a self) -> bool {
         self.as_object().is_some()
@@ -576,6 +1393,20 @@ impl Xml {
         }
     }
 
+    /// Returns true if the XML value is a DateTime. Returns false otherwise.
+    pub fn is_datetime(&self) -> bool {
+        self.as_datetime().is_some()
+    }
+
+    /// If the XML value is a DateTime, returns the associated DateTime.
+    /// Returns None otherwise.
+    pub fn as_datetime(&self) -> Option<DateTime> {
+        match *self {
+            Xml::DateTime(datetime) => Some(datetime),
+            _ => None
+        }
+    }
+
     /// Returns true if the XML value is a Null. Returns false otherwise.
     pub fn is_null(&self) -> bool {
         self.as_null().is_some()
@@ -640,20 +1471,83 @@ pub enum XmlEvent {
     StringEnd, // </string>
     NullStart, // <nil/>
     NullEnd, // <nil/>
-    // FIXME: datetime
-    // FIXME: Base64
+    DateTimeStart, // <dateTime.iso8601>
+    DateTimeValue(string::String),
+    DateTimeEnd, // </dateTime.iso8601>
+    MethodCallStart, // <methodCall>
+    MethodCallEnd, // </methodCall>
+    MethodNameStart, // <methodName>
+    MethodNameValue(string::String),
+    MethodNameEnd, // </methodName>
+    ParamsStart, // <params>
+    ParamsEnd, // </params>
+    ParamStart, // <param>
+    ParamEnd, // </param>
+    MethodResponseStart, // <methodResponse>
+    MethodResponseEnd, // </methodResponse>
+    FaultStart, // <fault>
+    FaultEnd, // </fault>
+    I8Start, // <ex:i8>, an Apache extension
+    I8Value(i64),
+    I8End, // </ex:i8>
+    Base64Start, // <base64>, or Apache's <ex:serializable>
+    Base64Value(Vec<u8>),
+    Base64End, // </base64>
     Error(ParserError) // FIXME: add error types
 }
 
-struct Builder<B: Buffer> {
+/// A structure to build XML values from an xml-rs event stream.
+pub struct Builder<B: Buffer> {
     parser: EventReader<B>,
     token: Option<XmlEvent>,
+    options: ParserOptions,
+    warnings: RefCell<Vec<Warning>>,
+    // an event read ahead, delivered by the next bump
+    pending: Option<XmlEvent>,
+    // the number of arrays and structs being built
+    depth: usize,
 }
 
 impl<B: Buffer> Builder<B> {
     /// Create an XML Builder.
     pub fn new(src: B) -> Builder<B> {
-        Builder { parser: EventReader::new(src), token: None, }
+        Builder::new_with(src, Default::default())
+    }
+
+    /// Create an XML Builder with the specified parser options.
+    pub fn new_with(src: B, options: ParserOptions) -> Builder<B> {
+        let parser = EventReader::new_with_config(src, options.reader_config());
+        Builder {
+            parser: parser,
+            token: None,
+            options: options,
+            warnings: RefCell::new(Vec::new()),
+            pending: None,
+            depth: 0,
+        }
+    }
+
+    /// Returns the deviations from the specification accepted so far.
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warnings.borrow().clone()
+    }
+
+    /// Prepares the builder to parse another document from `src`, keeping
+    /// its options, so one builder can serve many messages. All other state
+    /// is cleared, whether or not the last document parsed, while the
+    /// warnings keep their allocation. xml-rs cannot restart a reader on a
+    /// new source, so a new one is made for `src`.
+    pub fn reset(&mut self, src: B) {
+        let mut warnings = replace(&mut *self.warnings.borrow_mut(), Vec::new());
+        warnings.clear();
+        *self = Builder {
+            parser: EventReader::new_with_config(src, self.options.reader_config()),
+            token: None,
+            options: self.options,
+            warnings: RefCell::new(warnings),
+            pending: None,
+            depth: 0,
+        };
     }
 
 
@@ -664,40 +1558,173 @@ impl<B: Buffer> Builder<B> {
         match self.token {
             None => {}
             Some(XmlEvent::Error(e)) => { return Err(e); }
-            ref tok => { panic!("unexpected token {:?}", tok.clone()); }
-            // FIXME: we will need some way to parse a parameter only, and not error on </param>
-            // ?? make separate self.build_param()?
+            _ => { return Err(SyntaxError(TrailingCharacters,0,0)); }
         }
         result
     }
 
+    /// Builds a `<methodCall>` document into its method name and parameters.
+    pub fn build_method_call(&mut self) -> Result<(string::String, Vec<Xml>), BuilderError> {
+        self.bump(); // looking for <methodCall>
+        if self.token != Some(XmlEvent::MethodCallStart) {
+            return Err(SyntaxError(InvalidSyntax,0,0));
+        }
+        self.bump(); // looking for <methodName>
+        if self.token != Some(XmlEvent::MethodNameStart) {
+            return Err(SyntaxError(InvalidSyntax,0,0));
+        }
+        self.bump(); // looking for the method name
+        let method = match self.token {
+            Some(XmlEvent::MethodNameValue(ref s)) => s.to_string(),
+            _ => { return Err(SyntaxError(InvalidSyntax,0,0)); }
+        };
+        self.bump(); // looking for </methodName>
+        if self.token != Some(XmlEvent::MethodNameEnd) {
+            return Err(SyntaxError(InvalidSyntax,0,0));
+        }
+        self.bump(); // looking for <params>, which may be omitted
+        let params = match self.token {
+            Some(XmlEvent::ParamsStart) => {
+                let params = try!(self.build_params());
+                self.bump();
+                params
+            }
+            _ => Vec::new(),
+        };
+        match self.token {
+            Some(XmlEvent::MethodCallEnd) => Ok((method, params)),
+            Some(XmlEvent::Error(e)) => Err(e),
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        }
+    }
+
+    /// Builds a `<methodResponse>` document into either its parameters,
+    /// of which there may be none, or the value of its fault.
+    pub fn build_method_response(&mut self)
+                                 -> Result<Result<Vec<Xml>, Xml>, BuilderError> {
+        self.bump(); // looking for <methodResponse>
+        if self.token != Some(XmlEvent::MethodResponseStart) {
+            return Err(SyntaxError(InvalidSyntax,0,0));
+        }
+        self.bump(); // looking for <params> or <fault>, or neither
+        let result = match self.token {
+            Some(XmlEvent::ParamsStart) => {
+                let params = try!(self.build_params());
+                self.bump();
+                Ok(params)
+            }
+            Some(XmlEvent::FaultStart) => {
+                self.bump(); // looking for <value>
+                if self.token != Some(XmlEvent::ValueStart) {
+                    return Err(SyntaxError(InvalidSyntax,0,0));
+                }
+                self.bump();
+                let fault = try!(self.build_value());
+                self.bump(); // looking for </value>
+                if self.token != Some(XmlEvent::ValueEnd) {
+                    return Err(SyntaxError(InvalidSyntax,0,0));
+                }
+                self.bump(); // looking for </fault>
+                if self.token != Some(XmlEvent::FaultEnd) {
+                    return Err(SyntaxError(InvalidSyntax,0,0));
+                }
+                self.bump();
+                Err(fault)
+            }
+            _ => Ok(Vec::new()),
+        };
+        match self.token {
+            Some(XmlEvent::MethodResponseEnd) => Ok(result),
+            Some(XmlEvent::Error(e)) => Err(e),
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        }
+    }
+
+    fn build_params(&mut self) -> Result<Vec<Xml>, BuilderError> {
+        let mut params = Vec::new();
+        loop {
+            self.bump(); // looking for <param> or </params>
+            match self.token {
+                Some(XmlEvent::ParamsEnd) => { return Ok(params); }
+                Some(XmlEvent::ParamStart) => {}
+                None => { return Err(SyntaxError(EOFWhileParsingValue,0,0)); }
+                _ => { return Err(SyntaxError(InvalidSyntax,0,0)); }
+            }
+            self.bump(); // looking for <value>
+            if self.token != Some(XmlEvent::ValueStart) {
+                return Err(SyntaxError(InvalidSyntax,0,0));
+            }
+            self.bump(); // parse whatever value is inside
+            params.push(try!(self.build_value()));
+            self.bump(); // looking for </value>
+            if self.token != Some(XmlEvent::ValueEnd) {
+                return Err(SyntaxError(InvalidSyntax,0,0));
+            }
+            self.bump(); // looking for </param>
+            if self.token != Some(XmlEvent::ParamEnd) {
+                return Err(SyntaxError(InvalidSyntax,0,0));
+            }
+        }
+    }
+
     fn bump(&mut self) {
+        if let Some(token) = self.pending.take() {
+            self.token = Some(token);
+            return;
+        }
         let mut n = self.parser.next();
         loop {
             match n {
                 // FIXME: terser version
                 events::XmlEvent::StartDocument{version: _, encoding: _, standalone: _} => (),
+                // whitespace between tags is insignificant, except inside a string
+                events::XmlEvent::Whitespace(_) if self.token != Some(XmlEvent::StringStart) => (),
+                events::XmlEvent::StartElement { ref name, .. } if self.options.skip_unknown_elements &&
+                    self.parse_tag_start(name.local_name.as_slice()).is_none() => {
+                    self.warn(Warning::SkippedElement(name.local_name.clone()));
+                    self.skip_element();
+                }
                 _ => break,
             }
             n = self.parser.next();
         }
-        self.token = match n {
+        let token = match n {
             events::XmlEvent::StartElement { name, attributes: _, namespace: _ } => {
                 self.parse_tag_start(name.local_name.as_slice())
             }
             events::XmlEvent::EndElement { name } => {
                 self.parse_tag_end(name.local_name.as_slice())
             }
-            events::XmlEvent::Characters(s) => {
+            events::XmlEvent::Characters(s) | events::XmlEvent::Whitespace(s) => {
                 self.parse_tag_characters(s.as_slice(), &self.token)
             }
             events::XmlEvent::EndDocument => {
                 None
             }
             _ => None,
+        };
+        if self.token == Some(XmlEvent::ValueStart) && token == Some(XmlEvent::ValueEnd) {
+            // <value></value> is an empty untyped string
+            self.token = Some(XmlEvent::StringValue(string::String::new()));
+            self.pending = token;
+            return;
         }
+        self.token = token;
     }
    
+    // consumes the events of the element just started, up to its end
+    fn skip_element(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.parser.next() {
+                events::XmlEvent::StartElement { .. } => depth += 1,
+                events::XmlEvent::EndElement { .. } => depth -= 1,
+                events::XmlEvent::EndDocument | events::XmlEvent::Error(_) => return,
+                _ => {}
+            }
+        }
+    }
+
     /* try to figure out how to get row and col
     pub fn generate_syntax_error(&self, e: ErrorCode) -> BuilderError {
         SyntaxError(e, 
@@ -709,13 +1736,27 @@ impl<B: Buffer> Builder<B> {
     pub fn build_value(&mut self) -> Result<Xml, BuilderError> {
         match self.token {
             // all values must begin with opening tag
-            Some(XmlEvent::ObjectStart) => self.build_object(),
-            Some(XmlEvent::ArrayStart) => self.build_array(),
+            Some(XmlEvent::ObjectStart) | Some(XmlEvent::ArrayStart) => {
+                if self.depth >= self.options.max_depth {
+                    return Err(SyntaxError(NestingTooDeep,0,0));
+                }
+                self.depth += 1;
+                let value = if self.token == Some(XmlEvent::ObjectStart) {
+                    self.build_object()
+                } else {
+                    self.build_array()
+                };
+                self.depth -= 1;
+                value
+            }
             Some(XmlEvent::NullStart) => self.build_nil(),
             Some(XmlEvent::I32Start) => self.build_i32(),
             Some(XmlEvent::F64Start) => self.build_f64(),
             Some(XmlEvent::BooleanStart) => self.build_boolean(),
             Some(XmlEvent::StringStart) => self.build_string(),
+            Some(XmlEvent::DateTimeStart) => self.build_datetime(),
+            Some(XmlEvent::I8Start) => self.build_i8(),
+            Some(XmlEvent::Base64Start) => self.build_base64(),
             // error otherwise
             Some(XmlEvent::ObjectEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
             Some(XmlEvent::ArrayEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
@@ -735,8 +1776,28 @@ impl<B: Buffer> Builder<B> {
             Some(XmlEvent::I32Value(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
             Some(XmlEvent::F64Value(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
             Some(XmlEvent::BooleanValue(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
-            Some(XmlEvent::StringValue(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            // a value without a type element is a string
+            Some(XmlEvent::StringValue(ref s)) => Ok(Xml::String(s.clone())),
             Some(XmlEvent::NameValue(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::DateTimeEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::DateTimeValue(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodCallStart) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodCallEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodNameStart) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodNameValue(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodNameEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::ParamsStart) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::ParamsEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::ParamStart) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::ParamEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodResponseStart) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::MethodResponseEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::FaultStart) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::FaultEnd) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::I8Value(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::I8End) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::Base64Value(_)) => Err(SyntaxError(InvalidSyntax, 0, 0)),
+            Some(XmlEvent::Base64End) => Err(SyntaxError(InvalidSyntax, 0, 0)),
             Some(XmlEvent::Error(e)) => Err(e),
             None => Err(SyntaxError(EOFWhileParsingValue,0,0)),
         }
@@ -809,6 +1870,13 @@ impl<B: Buffer> Builder<B> {
                     Some(XmlEvent::ValueEnd) => (),
                     _ => { return Err(SyntaxError(InvalidSyntax,0,0)); }
                 }
+            } else {
+                match self.token {
+                    Some(XmlEvent::DataStart) | Some(XmlEvent::DataEnd) => (),
+                    Some(XmlEvent::Error(ref e)) => { return Err(e.clone()); }
+                    None => { return Err(SyntaxError(EOFWhileParsingArray,0,0)); }
+                    _ => { return Err(SyntaxError(InvalidSyntax,0,0)); }
+                }
             }
             self.bump();
         }
@@ -848,6 +1916,36 @@ impl<B: Buffer> Builder<B> {
         }
     }
 
+    fn build_i8(&mut self) -> Result<Xml, BuilderError> {
+        self.bump();
+        // 64-bit values beyond <int> are kept exact as strings, which the
+        // decoder reads back into integer types
+        let val = match self.token {
+            Some(XmlEvent::I8Value(v)) if v as i32 as i64 == v => Ok(Xml::I32(v as i32)),
+            Some(XmlEvent::I8Value(v)) => Ok(Xml::String(v.to_string())),
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        };
+        self.bump();
+        match self.token {
+            Some(XmlEvent::I8End) => val,
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        }
+    }
+
+    fn build_base64(&mut self) -> Result<Xml, BuilderError> {
+        self.bump();
+        let val = match self.token {
+            Some(XmlEvent::Base64Value(ref v)) => Ok(Xml::Base64(v.clone())),
+            Some(XmlEvent::Base64End) => return Ok(Xml::Base64(Vec::new())),
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        };
+        self.bump();
+        match self.token {
+            Some(XmlEvent::Base64End) => val,
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        }
+    }
+
     fn build_f64(&mut self) -> Result<Xml, BuilderError> {
         self.bump();
         let val = match self.token {
@@ -875,25 +1973,81 @@ impl<B: Buffer> Builder<B> {
         }
     }
 
+    fn build_datetime(&mut self) -> Result<Xml, BuilderError> {
+        self.bump();
+        let val = match self.token {
+            Some(XmlEvent::DateTimeValue(ref s)) => match DateTime::parse(s.as_slice()) {
+                Some(datetime) => Ok(Xml::DateTime(datetime)),
+                None => Err(SyntaxError(InvalidSyntax,0,0)),
+            },
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        };
+        self.bump();
+        match self.token {
+            Some(XmlEvent::DateTimeEnd) => val,
+            _ => Err(SyntaxError(InvalidSyntax,0,0)),
+        }
+    }
+
     fn parse_bool_value(&self, s: &str) -> Option<XmlEvent> {
         match s {
             "0" => Some(XmlEvent::BooleanValue(false)),
             "1" => Some(XmlEvent::BooleanValue(true)),
+            _ if self.options.lenient_booleans => {
+                let value = if s.eq_ignore_ascii_case("true") {
+                    true
+                } else if s.eq_ignore_ascii_case("false") {
+                    false
+                } else {
+                    return None;
+                };
+                self.warn(Warning::LenientBoolean(s.to_string()));
+                Some(XmlEvent::BooleanValue(value))
+            }
             _ => None
         }
     }
 
     fn parse_i32_value(&self, s: &str) -> Option<XmlEvent> {
-        match s.parse::<i32>() {
-            Some(n) => Some(XmlEvent::I32Value(n)),
-            None => None
+        // the one deviation accepted in <int> is a '+' in place of a '-'
+        let lenient = self.options.lenient_numbers && s.starts_with("+");
+        let digits = if lenient { &s[1..] } else { s };
+        if (lenient && digits.starts_with("-")) || !is_spec_number(digits, false) {
+            return None;
+        }
+        let n = match digits.parse::<i32>() {
+            Some(n) => n,
+            None => return None,
+        };
+        if lenient {
+            self.warn(Warning::LenientNumber(s.to_string()));
         }
+        Some(XmlEvent::I32Value(n))
     }
     fn parse_f64_value(&self, s: &str) -> Option<XmlEvent> {
-        match s.parse::<f64>() {
-            Some(n) => Some(XmlEvent::F64Value(n)),
-            None => None
+        let lenient = !is_spec_number(s, true);
+        if lenient && !(self.options.lenient_numbers && is_lenient_number(s)) {
+            return None;
+        }
+        let digits = if s.starts_with("+") { &s[1..] } else { s };
+        // XML-RPC has no infinities, which an overlong number parses to
+        let n = match digits.parse::<f64>() {
+            Some(n) if n.is_finite() => n,
+            _ => return None,
+        };
+        if lenient {
+            self.warn(Warning::LenientNumber(s.to_string()));
+        }
+        Some(XmlEvent::F64Value(n))
+    }
+    fn parse_i8_value(&self, s: &str) -> Option<XmlEvent> {
+        if !is_spec_number(s, false) {
+            return None;
         }
+        s.parse::<i64>().map(XmlEvent::I8Value)
+    }
+    fn parse_base64_value(&self, s: &str) -> Option<XmlEvent> {
+        s.from_base64().ok().map(XmlEvent::Base64Value)
     }
     fn parse_string_value(&self, s: &str) -> Option<XmlEvent> {
         Some(XmlEvent::StringValue(s.to_string()))
@@ -901,6 +2055,12 @@ impl<B: Buffer> Builder<B> {
     fn parse_name_value(&self, s: &str) -> Option<XmlEvent> {
         Some(XmlEvent::NameValue(s.to_string()))
     }
+    fn parse_datetime_value(&self, s: &str) -> Option<XmlEvent> {
+        Some(XmlEvent::DateTimeValue(s.to_string()))
+    }
+    fn parse_method_name_value(&self, s: &str) -> Option<XmlEvent> {
+        Some(XmlEvent::MethodNameValue(s.to_string()))
+    }
     fn parse_tag_start(&self, name: &str) -> Option<XmlEvent> {
         return match name {
             "struct" => Some(XmlEvent::ObjectStart),
@@ -910,10 +2070,21 @@ impl<B: Buffer> Builder<B> {
             "array" => Some(XmlEvent::ArrayStart),
             "data" => Some(XmlEvent::DataStart),
             "boolean" => Some(XmlEvent::BooleanStart),
-            "int" => Some(XmlEvent::I32Start),
+            "int" | "i4" => Some(XmlEvent::I32Start),
             "double" => Some(XmlEvent::F64Start),
             "string" => Some(XmlEvent::StringStart),
             "nil" => Some(XmlEvent::NullStart),
+            "dateTime.iso8601" => Some(XmlEvent::DateTimeStart),
+            "methodCall" => Some(XmlEvent::MethodCallStart),
+            "methodName" => Some(XmlEvent::MethodNameStart),
+            "params" => Some(XmlEvent::ParamsStart),
+            "param" => Some(XmlEvent::ParamStart),
+            "methodResponse" => Some(XmlEvent::MethodResponseStart),
+            "fault" => Some(XmlEvent::FaultStart),
+            "base64" => Some(XmlEvent::Base64Start),
+            "i8" if self.options.apache_extensions => Some(XmlEvent::I8Start),
+            "dateTime" if self.options.apache_extensions => Some(XmlEvent::DateTimeStart),
+            "serializable" if self.options.apache_extensions => Some(XmlEvent::Base64Start),
             _ => None,
         }
     }
@@ -927,41 +2098,239 @@ impl<B: Buffer> Builder<B> {
             "array" => Some(XmlEvent::ArrayEnd),
             "data" => Some(XmlEvent::DataEnd),
             "boolean" => Some(XmlEvent::BooleanEnd),
-            "int" => Some(XmlEvent::I32End),
+            "int" | "i4" => Some(XmlEvent::I32End),
             "double" => Some(XmlEvent::F64End),
             "string" => Some(XmlEvent::StringEnd),
             "nil" => Some(XmlEvent::NullEnd),
+            "dateTime.iso8601" => Some(XmlEvent::DateTimeEnd),
+            "methodCall" => Some(XmlEvent::MethodCallEnd),
+            "methodName" => Some(XmlEvent::MethodNameEnd),
+            "params" => Some(XmlEvent::ParamsEnd),
+            "param" => Some(XmlEvent::ParamEnd),
+            "methodResponse" => Some(XmlEvent::MethodResponseEnd),
+            "fault" => Some(XmlEvent::FaultEnd),
+            "base64" => Some(XmlEvent::Base64End),
+            "i8" if self.options.apache_extensions => Some(XmlEvent::I8End),
+            "dateTime" if self.options.apache_extensions => Some(XmlEvent::DateTimeEnd),
+            "serializable" if self.options.apache_extensions => Some(XmlEvent::Base64End),
             _ => None,
         }
     }
 
+    fn warn(&self, warning: Warning) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
     fn parse_tag_characters(&self, s: &str, token: &Option<XmlEvent>) -> Option<XmlEvent> {
+        let scalar = if self.options.trim_scalars { s.trim() } else { s };
+        let is_scalar = match token {
+            &Some(XmlEvent::BooleanStart) | &Some(XmlEvent::I32Start) |
+            &Some(XmlEvent::F64Start) | &Some(XmlEvent::DateTimeStart) |
+            &Some(XmlEvent::I8Start) => true,
+            _ => false,
+        };
+        if is_scalar && scalar.len() != s.len() {
+            self.warn(Warning::TrimmedScalar(s.to_string()));
+        }
         match token {
-            &Some(XmlEvent::BooleanStart) => self.parse_bool_value(s),
-            &Some(XmlEvent::I32Start) => self.parse_i32_value(s),
-            &Some(XmlEvent::F64Start) => self.parse_f64_value(s),
+            &Some(XmlEvent::BooleanStart) => self.parse_bool_value(scalar),
+            &Some(XmlEvent::I32Start) => self.parse_i32_value(scalar),
+            &Some(XmlEvent::F64Start) => self.parse_f64_value(scalar),
+            &Some(XmlEvent::DateTimeStart) => self.parse_datetime_value(scalar),
+            &Some(XmlEvent::I8Start) => self.parse_i8_value(scalar),
+            &Some(XmlEvent::Base64Start) => self.parse_base64_value(s),
             &Some(XmlEvent::StringStart) => self.parse_string_value(s),
+            &Some(XmlEvent::ValueStart) => self.parse_string_value(s),
             &Some(XmlEvent::NameStart) => self.parse_name_value(s),
+            &Some(XmlEvent::MethodNameStart) => self.parse_method_name_value(s),
             _ => None,
         }
     }
 }
 
+/// Returns true if `s` is a number as permitted by the XML-RPC spec: an
+/// optional '-' followed by digits, with a single '.' allowed for doubles.
+fn is_spec_number(s: &str, allow_point: bool) -> bool {
+    let digits = if s.starts_with("-") { &s[1..] } else { s };
+    let mut seen_digit = false;
+    let mut seen_point = false;
+    for c in digits.chars() {
+        match c {
+            '0'...'9' => seen_digit = true,
+            '.' if allow_point && !seen_point => seen_point = true,
+            _ => return false,
+        }
+    }
+    seen_digit
+}
+
+/// Returns true if `s` is a number in the wider form lenient parsing
+/// accepts: an optional sign, digits with at most one '.', and an optional
+/// exponent. Names such as "inf" and "NaN" are not numbers.
+fn is_lenient_number(s: &str) -> bool {
+    fn unsigned(s: &str) -> &str {
+        if s.starts_with("+") || s.starts_with("-") { &s[1..] } else { s }
+    }
+    let s = unsigned(s);
+    let (mantissa, exponent) = match s.find(|c: char| c == 'e' || c == 'E') {
+        Some(idx) => (&s[..idx], Some(unsigned(&s[idx + 1..]))),
+        None => (s, None),
+    };
+    let exponent_ok = match exponent {
+        Some(e) => !e.is_empty() && e.chars().all(|c| c >= '0' && c <= '9'),
+        None => true,
+    };
+    !mantissa.starts_with("-") && is_spec_number(mantissa, true) && exponent_ok
+}
+
 /// A structure to decode JSON to values in rust.
 pub struct Decoder {
     stack: Vec<Xml>,
+    options: DecoderOptions,
+    unknown: Vec<Object>,
+    path: Vec<string::String>,
+    // set while decoding a struct field whose member is absent, until
+    // read_option claims it
+    absent: bool,
+    // set by read_struct when the struct may be a newtype read transparently,
+    // until its first field is read
+    newtype: bool,
+    // set while decoding a map key, which arrives as the member name string
+    map_key: bool,
+    warnings: Vec<Warning>,
+}
+
+/// Options controlling how XML values are decoded into rust values.
+#[derive(Clone, PartialEq, Show)]
+pub struct DecoderOptions {
+    /// Struct member names to use in place of rust field names, keyed by
+    /// field name. Applies to every struct being decoded.
+    pub renames: HashMap<string::String, string::String>,
+    /// Match struct member names without regard to ASCII case
+    pub case_insensitive: bool,
+    /// What to do with struct members that match no rust field
+    pub unknown_members: UnknownMembers,
+    /// Convert between scalar types where the intent is unambiguous: numeric
+    /// strings and booleans to numbers, 0 and 1 to booleans, integral
+    /// doubles to integers and scalars to strings
+    pub coerce: bool,
+    /// Decode an `Option` field as None when its struct member is absent
+    pub missing_as_none: bool,
+    /// Decode an `Option` as None from an empty string, as some servers
+    /// send in place of `<nil/>`
+    pub empty_as_none: bool,
+    /// Read a tuple struct with a single field from that field's value, as
+    /// `EncoderOptions::transparent_newtypes` writes it, rather than from a
+    /// struct with a `_field0` member. Off by default.
+    pub transparent_newtypes: bool,
+    /// With `Double`, read integral doubles beyond the range of `<int>`
+    /// into integer types, as `EncoderOptions::large_integers` writes them
+    pub large_integers: LargeIntegers,
+}
+
+/// Policy for struct members that do not correspond to any rust field.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum UnknownMembers {
+    /// Silently drop unknown members
+    Ignore,
+    /// Fail with an `UnknownMemberError` naming the first unknown member
+    Deny,
+    /// Keep unknown members, retrievable through `Decoder::unknown_members`
+    Collect,
+}
+
+impl DecoderOptions {
+    /// Decodes the rust field `field` from the struct member named `member`.
+    pub fn rename(mut self, field: &str, member: &str) -> DecoderOptions {
+        self.renames.insert(field.to_string(), member.to_string());
+        self
+    }
+}
+
+impl Default for DecoderOptions {
+    fn default() -> DecoderOptions {
+        DecoderOptions {
+            renames: HashMap::new(),
+            case_insensitive: false,
+            unknown_members: UnknownMembers::Ignore,
+            coerce: false,
+            missing_as_none: true,
+            empty_as_none: false,
+            transparent_newtypes: false,
+            large_integers: LargeIntegers::Truncate,
+        }
+    }
 }
 
 impl Decoder {
     /// Creates a new decoder instance for decoding the specified XML value.
     pub fn new(xml: Xml) -> Decoder {
-        Decoder { stack: vec![xml] }
+        Decoder::new_with(xml, Default::default())
+    }
+
+    /// Creates a new decoder instance with the specified options.
+    pub fn new_with(xml: Xml, options: DecoderOptions) -> Decoder {
+        Decoder { stack: vec![xml], options: options, unknown: Vec::new(), path: Vec::new(),
+                  absent: false, newtype: false, map_key: false, warnings: Vec::new() }
+    }
+
+    /// Returns the unknown members of every struct decoded so far, in the
+    /// order the structs were completed. Only populated when the decoder was
+    /// created with `UnknownMembers::Collect`.
+    pub fn unknown_members(&self) -> &[Object] {
+        self.unknown.as_slice()
+    }
+
+    /// Returns the type conversions performed so far.
+    pub fn warnings(&self) -> &[Warning] {
+        self.warnings.as_slice()
     }
 }
 
 impl Decoder {
     fn pop(&mut self) -> Xml {
-        self.stack.pop().unwrap()
+        // a Decodable impl reading more values than it was given sees nil,
+        // which fails its type check rather than panicking
+        self.stack.pop().unwrap_or(Xml::Null)
+    }
+
+    fn coerced(&mut self, from: &'static str, to: &'static str) {
+        let path = format!("value{}", self.path.concat());
+        self.warnings.push(Warning::Coerced(path, from, to));
+    }
+
+    /// Leaves the innermost path segment, attaching the full path to an error
+    /// raised within it unless a deeper segment already has.
+    fn leave_path<T>(&mut self, result: DecodeResult<T>) -> DecodeResult<T> {
+        let result = match result {
+            Err(PathError(path, e)) => Err(PathError(path, e)),
+            Err(e) => {
+                let path = format!("value{}", self.path.concat());
+                Err(PathError(path, Box::new(e)))
+            }
+            ok => ok,
+        };
+        self.path.pop();
+        result
+    }
+
+    /// Finds the key of the member in `obj` holding the rust field `name`.
+    fn member_key(&self, obj: &Object, name: &str) -> Option<string::String> {
+        let member = match self.options.renames.get(name) {
+            Some(member) => member.as_slice(),
+            None => name,
+        };
+        if obj.contains_key(member) {
+            return Some(member.to_string());
+        }
+        if self.options.case_insensitive {
+            for key in obj.keys() {
+                if key.as_slice().eq_ignore_ascii_case(member) {
+                    return Some(key.clone());
+                }
+            }
+        }
+        None
     }
 }
 
@@ -992,11 +2361,32 @@ macro_rules! read_primitive {
                     Some(f) => Ok(f),
                     None => Err(ExpectedError("Number".to_string(), format!("{}", f))),
                 },
+                Xml::F64(f) if f.fract() == 0.0 && self.options.coerce => match num::cast(f) {
+                    Some(n) => { self.coerced("double", "integer"); Ok(n) }
+                    None => Err(ExpectedError("Number".to_string(), format!("{}", f))),
+                },
+                // integral doubles beyond the range of <int> are large integers
+                Xml::F64(f) if f.fract() == 0.0 && f.abs() > i32::MAX as f64 &&
+                    self.options.large_integers == LargeIntegers::Double => match num::cast(f) {
+                    Some(n) => Ok(n),
+                    None => Err(ExpectedError("Number".to_string(), format!("{}", f))),
+                },
                 Xml::F64(f) => Err(ExpectedError("Integer".to_string(), format!("{}", f))),
                 Xml::String(s) => match s.parse() {
-                    Some(f) => Ok(f),
+                    Some(f) => {
+                        // map keys and integers beyond <int> are strings by
+                        // design; any other number in a string was coerced
+                        if !self.map_key && s.parse::<i32>().is_some() {
+                            self.coerced("string", "integer");
+                        }
+                        Ok(f)
+                    }
                     None => Err(ExpectedError("Number".to_string(), s)),
                 },
+                Xml::Boolean(b) if self.options.coerce => match num::cast(b as u8) {
+                    Some(f) => { self.coerced("boolean", "integer"); Ok(f) }
+                    None => Err(ExpectedError("Number".to_string(), format!("{}", b))),
+                },
                 value => Err(ExpectedError("Number".to_string(), format!("{}", value))),
             }
         }
@@ -1007,7 +2397,12 @@ impl SerializeDecoder for Decoder {
     type Error = DecoderError;
 
     fn read_nil(&mut self) -> DecodeResult<()> {
-        expect!(self.pop(), Null)
+        match self.pop() {
+            Xml::Null => Ok(()),
+            Xml::String(ref s) if s.is_empty() => Ok(()),
+            Xml::Object(ref obj) if obj.is_empty() => Ok(()),
+            other => Err(ExpectedError("Null".to_string(), format!("{}", other))),
+        }
     }
 
     read_primitive! { read_usize, usize }
@@ -1031,9 +2426,12 @@ impl SerializeDecoder for Decoder {
                 // re: #12967.. a type w/ numeric keys (ie HashMap<usize, V> etc)
                 // is going to have a string here, as per JSON spec.
                 match s.parse() {
-                    Some(f) => Ok(f),
+                    Some(f) => {
+                        if !self.map_key { self.coerced("string", "double"); }
+                        Ok(f)
+                    }
                     None => Err(ExpectedError("Number".to_string(), s)),
-                } 
+                }
             },
             Xml::Null => Ok(f64::NAN), // FIXME: does this exist for XML?
             value => Err(ExpectedError("Number".to_string(), format!("{}", value)))
@@ -1041,7 +2439,22 @@ impl SerializeDecoder for Decoder {
     }
 
     fn read_bool(&mut self) -> DecodeResult<bool> {
-        expect!(self.pop(), Boolean)
+        match self.pop() {
+            Xml::Boolean(b) => Ok(b),
+            Xml::I32(n) if self.options.coerce && (n == 0 || n == 1) => {
+                self.coerced("int", "boolean");
+                Ok(n == 1)
+            }
+            // map keys are decoded from member names
+            Xml::String(s) => match (s.parse(), s.as_slice()) {
+                (Some(b), _) if self.map_key => Ok(b),
+                (Some(b), _) if self.options.coerce => { self.coerced("string", "boolean"); Ok(b) }
+                (None, "0") if self.options.coerce => { self.coerced("string", "boolean"); Ok(false) }
+                (None, "1") if self.options.coerce => { self.coerced("string", "boolean"); Ok(true) }
+                _ => Err(ExpectedError("Boolean".to_string(), s)),
+            },
+            value => Err(ExpectedError("Boolean".to_string(), format!("{}", value))),
+        }
     }
 
     fn read_char(&mut self) -> DecodeResult<char> {
@@ -1054,11 +2467,26 @@ impl SerializeDecoder for Decoder {
                 _ => ()
             }
         }
-        Err(ExpectedError("single character string".to_string(), format!("{}", s)))
+        // a base character and its combining marks are several chars
+        let found = match s.chars().count() {
+            0 => "empty string".to_string(),
+            n => format!("{}-character string \"{}\"", n, s),
+        };
+        Err(ExpectedError("single character string".to_string(), found))
     }
 
     fn read_str(&mut self) -> DecodeResult<string::String> {
-        expect!(self.pop(), String)
+        match self.pop() {
+            Xml::String(s) => Ok(s),
+            Xml::DateTime(datetime) => Ok(datetime.to_string()),
+            Xml::I32(n) if self.options.coerce => { self.coerced("int", "string"); Ok(n.to_string()) }
+            Xml::F64(f) if self.options.coerce => { self.coerced("double", "string"); Ok(f.to_string()) }
+            Xml::Boolean(b) if self.options.coerce => {
+                self.coerced("boolean", "string");
+                Ok(b.to_string())
+            }
+            value => Err(ExpectedError("String".to_string(), format!("{}", value))),
+        }
     }
 
     fn read_enum<T, F>(&mut self, _name: &str, f: F) -> DecodeResult<T> where
@@ -1132,11 +2560,45 @@ impl SerializeDecoder for Decoder {
         self.read_enum_variant_arg(idx, f)
     }
 
-    fn read_struct<T, F>(&mut self, _name: &str, _len: usize, f: F) -> DecodeResult<T> where
+    fn read_struct<T, F>(&mut self, name: &str, len: usize, f: F) -> DecodeResult<T> where
         F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
+        if name == DATETIME_STRUCT {
+            // present the dateTime as the struct its Decodable impl expects
+            let text = match self.pop() {
+                Xml::DateTime(datetime) => datetime.to_string(),
+                Xml::String(s) => s,
+                value => return Err(ExpectedError("DateTime".to_string(), format!("{}", value))),
+            };
+            let mut obj = BTreeMap::new();
+            obj.insert("iso8601".to_string(), Xml::String(text));
+            self.stack.push(Xml::Object(obj));
+        }
+        if len == 0 {
+            // a unit struct, which may have been written as nil or ""
+            match self.pop() {
+                Xml::Null => self.stack.push(Xml::Object(BTreeMap::new())),
+                Xml::String(ref s) if s.is_empty() => self.stack.push(Xml::Object(BTreeMap::new())),
+                value => self.stack.push(value),
+            }
+        }
+        self.newtype = len == 1 && self.options.transparent_newtypes;
         let value = try!(f(self));
-        self.pop();
+        // whatever members remain were not claimed by any field
+        let remaining = try!(expect!(self.pop(), Object));
+        match self.options.unknown_members {
+            UnknownMembers::Ignore => {}
+            UnknownMembers::Deny => {
+                if let Some(key) = remaining.keys().next() {
+                    return Err(UnknownMemberError(key.clone()));
+                }
+            }
+            UnknownMembers::Collect => {
+                if !remaining.is_empty() {
+                    self.unknown.push(remaining);
+                }
+            }
+        }
         Ok(value)
     }
 
@@ -1147,23 +2609,43 @@ impl SerializeDecoder for Decoder {
                                -> DecodeResult<T> where
         F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
+        if self.newtype {
+            self.newtype = false;
+            let wrapped = match self.stack.last() {
+                Some(&Xml::Object(ref obj)) => obj.contains_key(NEWTYPE_FIELD),
+                _ => false,
+            };
+            if name == NEWTYPE_FIELD && !wrapped {
+                // the value on the stack is the field itself
+                let value = try!(f(self));
+                self.stack.push(Xml::Object(BTreeMap::new()));
+                return Ok(value);
+            }
+        }
         let mut obj = try!(expect!(self.pop(), Object));
 
-        let value = match obj.remove(&name.to_string()) {
+        self.path.push(format!(".{}", name));
+        let member = self.member_key(&obj, name);
+        let result = match member.and_then(|key| obj.remove(&key)) {
             None => {
-                // Add a Null and try to parse it as an Option<_>
-                // to get None as a default value.
+                // Decode a placeholder; only an Option<_> field claims it
+                // and yields None, anything else is a missing field.
                 self.stack.push(Xml::Null);
-                match f(self) {
-                    Ok(x) => x,
-                    Err(_) => return Err(MissingFieldError(name.to_string())),
+                self.absent = true;
+                let result = f(self);
+                let claimed = !self.absent;
+                self.absent = false;
+                match result {
+                    Ok(x) if claimed => Ok(x),
+                    _ => Err(MissingFieldError(name.to_string())),
                 }
             },
             Some(xml) => {
                 self.stack.push(xml);
-                try!(f(self))
+                f(self)
             }
         };
+        let value = try!(self.leave_path(result));
         self.stack.push(Xml::Object(obj));
         Ok(value)
     }
@@ -1208,8 +2690,17 @@ impl SerializeDecoder for Decoder {
     fn read_option<T, F>(&mut self, mut f: F) -> DecodeResult<T> where
         F: FnMut(&mut Decoder, bool) -> DecodeResult<T>,
     {
+        if self.absent {
+            self.absent = false;
+            if !self.options.missing_as_none {
+                return Err(MissingFieldError("Option".to_string()));
+            }
+            self.pop();
+            return f(self, false);
+        }
         match self.pop() {
             Xml::Null => f(self, false),
+            Xml::String(ref s) if s.is_empty() && self.options.empty_as_none => f(self, false),
             value => { self.stack.push(value); f(self, true) }
         }
     }
@@ -1225,10 +2716,12 @@ impl SerializeDecoder for Decoder {
         f(self, len)
     }
 
-    fn read_seq_elt<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T> where
+    fn read_seq_elt<T, F>(&mut self, idx: usize, f: F) -> DecodeResult<T> where
         F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
-        f(self)
+        self.path.push(format!("[{}]", idx));
+        let result = f(self);
+        self.leave_path(result)
     }
 
     fn read_map<T, F>(&mut self, f: F) -> DecodeResult<T> where
@@ -1236,7 +2729,8 @@ impl SerializeDecoder for Decoder {
     {
         let obj = try!(expect!(self.pop(), Object));
         let len = obj.len();
-        for (key, value) in obj.into_iter() {
+        // pushed in reverse so that members are read in order of name
+        for (key, value) in obj.into_iter().rev() {
             self.stack.push(value);
             self.stack.push(Xml::String(key));
         }
@@ -1246,7 +2740,10 @@ impl SerializeDecoder for Decoder {
     fn read_map_elt_key<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T> where
        F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
-        f(self)
+        self.map_key = true;
+        let key = f(self);
+        self.map_key = false;
+        key
     }
     fn read_map_elt_val<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T> where
        F: FnOnce(&mut Decoder) -> DecodeResult<T>,
@@ -1275,8 +2772,24 @@ macro_rules! to_xml_impl_i32 {
     )
 }
 
-to_xml_impl_i32! { isize, i8, i16, i32, i64 }
-to_xml_impl_i32! { usize, u8, u16, u32, u64 }
+// integers wider than <int> are kept exact as strings when out of its
+// range, as the builder keeps <ex:i8> values; the decoder reads them back
+macro_rules! to_xml_impl_large {
+    ($($t:ty), +) => (
+        $(impl ToXml for $t {
+            fn to_xml(&self) -> Xml {
+                match num::cast::<$t, i32>(*self) {
+                    Some(v) => Xml::I32(v),
+                    None => Xml::String(self.to_string()),
+                }
+            }
+        })+
+    )
+}
+
+to_xml_impl_i32! { i8, i16, i32 }
+to_xml_impl_i32! { u8, u16 }
+to_xml_impl_large! { isize, i64, usize, u32, u64 }
 
 impl ToXml for Xml {
     fn to_xml(&self) -> Xml { self.clone() }
@@ -1300,6 +2813,38 @@ impl ToXml for f64 {
     }
 }
 
+impl ToXml for DateTime {
+    fn to_xml(&self) -> Xml { Xml::DateTime(*self) }
+}
+
+/// Written in UTC as dateTime.iso8601, since XML-RPC carries no time zone
+impl ToXml for time::Tm {
+    fn to_xml(&self) -> Xml { self.to_timespec().to_xml() }
+}
+
+/// Written in UTC as dateTime.iso8601, or as nil for an instant outside
+/// the years that form can express
+impl ToXml for time::Timespec {
+    fn to_xml(&self) -> Xml {
+        DateTime::from_timespec(*self).map(Xml::DateTime).unwrap_or(Xml::Null)
+    }
+}
+
+/// Durations are written as a number of seconds: an `<int>` when whole and
+/// within range, a `<double>` otherwise.
+impl ToXml for Duration {
+    fn to_xml(&self) -> Xml {
+        let secs = self.num_seconds();
+        if *self == Duration::seconds(secs) && secs as i32 as i64 == secs {
+            return Xml::I32(secs as i32);
+        }
+        match self.num_nanoseconds() {
+            Some(ns) => Xml::F64(ns as f64 / 1e9),
+            None => Xml::F64(secs as f64),
+        }
+    }
+}
+
 impl ToXml for () {
     fn to_xml(&self) -> Xml { Xml::Null }
 }
@@ -1356,6 +2901,37 @@ impl<A: ToXml> ToXml for Vec<A> {
     fn to_xml(&self) -> Xml { Xml::Array(self.iter().map(|elt| elt.to_xml()).collect()) }
 }
 
+macro_rules! array_impl {
+    ($($n:expr),+) => (
+        $(impl<A: ToXml> ToXml for [A; $n] {
+            fn to_xml(&self) -> Xml { self.as_slice().to_xml() }
+        })+
+    )
+}
+
+array_impl! { 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16 }
+array_impl! { 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32 }
+
+impl<'a, A: ?Sized + ToXml> ToXml for &'a A {
+    fn to_xml(&self) -> Xml { (**self).to_xml() }
+}
+
+impl<A: ?Sized + ToXml> ToXml for Box<A> {
+    fn to_xml(&self) -> Xml { (**self).to_xml() }
+}
+
+impl<A: ToXml> ToXml for Rc<A> {
+    fn to_xml(&self) -> Xml { (**self).to_xml() }
+}
+
+impl<A: ToXml> ToXml for Arc<A> {
+    fn to_xml(&self) -> Xml { (**self).to_xml() }
+}
+
+impl<'a> ToXml for string::CowString<'a> {
+    fn to_xml(&self) -> Xml { Xml::String(self.to_string()) }
+}
+
 impl<A: ToXml> ToXml for BTreeMap<string::String, A> {
     fn to_xml(&self) -> Xml {
         let mut d = BTreeMap::new();
@@ -1413,15 +2989,693 @@ impl<'a, T: Encodable> fmt::String for AsXml<'a, T> {
     }
 }
 
-/*
 impl FromStr for Xml {
+    /// Parses an XML value, allowing `let v: Option<Xml> = s.parse()`
     fn from_str(s: &str) -> Option<Xml> {
         Xml::from_str(s).ok()
     }
 }
-*/
 
 #[cfg(test)]
 mod tests {
+    use super::{encode, encode_with, decode, decode_with, DecoderOptions, EncoderOptions};
+    use super::{array_from_iter, Encoder, ValueWriter};
+    use super::{IntTag, LargeIntegers, Members, Profile, Units, Xml, parse_method_call, parse_method_response};
+    use super::{ParserOptions, DEFAULT_MAX_DEPTH, ToXml, from_value};
+    use super::{Builder, Decoder, DecoderError, ParserError, UnknownMembers, Warning};
+    use rustc_serialize::Decodable;
+    use super::ParserError::SyntaxError;
+    use super::ErrorCode::NestingTooDeep;
+    use std::collections::{HashMap, BTreeMap};
+    use std::default::Default;
+    use std::io::BufReader;
+    use std::{string, u32, u64};
+
+    #[derive(RustcEncodable, RustcDecodable, PartialEq, Show)]
+    struct Optional {
+        name: Option<string::String>,
+        count: Option<i32>,
+    }
+
+    #[test]
+    fn test_encode_map_with_int_keys() {
+        let mut map = BTreeMap::new();
+        map.insert(1i32, 2i32);
+        assert_eq!(encode(&map).as_slice(),
+                   "<struct><member><name>1</name><value><int>2</int></value></member></struct>");
+    }
+
+    #[test]
+    fn test_decode_hashmap_with_int_keys() {
+        let mut map = HashMap::new();
+        map.insert(1i32, "one".to_string());
+        map.insert(-2i32, "minus two".to_string());
+        let decoded: HashMap<i32, string::String> = decode(encode(&map).as_slice()).unwrap();
+        assert_eq!(decoded, map);
+    }
+
+    #[test]
+    fn test_decode_btreemap_with_int_keys() {
+        let mut map = BTreeMap::new();
+        map.insert(10u8, true);
+        map.insert(20u8, false);
+        let decoded: BTreeMap<u8, bool> = decode(encode(&map).as_slice()).unwrap();
+        assert_eq!(decoded, map);
+    }
+
+    #[test]
+    fn test_decode_bool_from_string() {
+        let mut map = BTreeMap::new();
+        map.insert(true, 1i32);
+        let decoded: BTreeMap<bool, i32> = decode(encode(&map).as_slice()).unwrap();
+        assert_eq!(decoded, map);
+
+        // outside map keys a string is a boolean only when coercing
+        assert!(decode::<bool>("<string>true</string>").is_err());
+        let options = DecoderOptions { coerce: true, ..Default::default() };
+        assert_eq!(decode_with::<bool>("<string>true</string>", Default::default(), options),
+                   Ok(true));
+    }
+
+    #[test]
+    fn test_decode_option_fields() {
+        let absent: Optional = decode("<struct></struct>").unwrap();
+        assert_eq!(absent, Optional { name: None, count: None });
+
+        let xml = "<struct><member><name>name</name><value><string></string></value></member>\
+                   <member><name>count</name><value><nil/></value></member></struct>";
+        let empty: Optional = decode(xml).unwrap();
+        assert_eq!(empty, Optional { name: Some("".to_string()), count: None });
+
+        let options = DecoderOptions { empty_as_none: true, ..Default::default() };
+        let empty: Optional = decode_with(xml, Default::default(), options).unwrap();
+        assert_eq!(empty, Optional { name: None, count: None });
+    }
+
+    #[test]
+    fn test_decode_option_fields_required() {
+        let options = DecoderOptions { missing_as_none: false, ..Default::default() };
+        let result: Result<Optional, _> = decode_with("<struct></struct>", Default::default(), options);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_encode_skip_nil() {
+        let value = Optional { name: Some("x".to_string()), count: None };
+        let options = EncoderOptions { skip_nil: true, ..Default::default() };
+        assert_eq!(encode_with(&value, options).as_slice(),
+                   "<struct><member><name>name</name><value><string>x</string></value></member></struct>");
+        assert_eq!(encode(&value).as_slice(),
+                   "<struct><member><name>name</name><value><string>x</string></value></member>\
+                    <member><name>count</name><value><nil/></value></member></struct>");
+    }
+
+    #[derive(Clone, Copy, PartialEq, Show)]
+    enum Status { Active, Suspended, Deleted }
+    xmlrpc_enum_codes!(Status { Active = 0, Suspended = 1, Deleted = 2 });
+
+    #[derive(Clone, Copy, PartialEq, Show)]
+    enum Level { Low, High }
+    xmlrpc_enum_names!(Level { Low = "low", High = "HIGH" });
+
+    #[test]
+    fn test_enum_codes_and_names() {
+        assert_eq!(encode(&Status::Suspended).as_slice(), "<int>1</int>");
+        assert_eq!(decode::<Status>("<int>2</int>").unwrap(), Status::Deleted);
+        assert!(decode::<Status>("<int>3</int>").is_err());
+        assert_eq!(encode(&Level::High).as_slice(), "<string>HIGH</string>");
+        assert_eq!(decode::<Level>("<string>low</string>").unwrap(), Level::Low);
+        assert!(decode::<Level>("<string>Low</string>").is_err());
+    }
+
+    #[derive(RustcEncodable, RustcDecodable, PartialEq, Show)]
+    struct UserId(i32);
+
+    #[derive(RustcEncodable, RustcDecodable, PartialEq, Show)]
+    struct Named { id: UserId }
+
+    #[test]
+    fn test_transparent_newtypes() {
+        // the default keeps the derived format
+        let wrapped = encode(&UserId(7));
+        assert_eq!(wrapped.as_slice(),
+                   "<struct><member><name>_field0</name><value><int>7</int></value></member></struct>");
+        assert_eq!(decode::<UserId>(wrapped.as_slice()).unwrap(), UserId(7));
+
+        let named = Named { id: UserId(7) };
+        let options = EncoderOptions { transparent_newtypes: true, ..Default::default() };
+        let encoded = encode_with(&named, options);
+        assert_eq!(encoded.as_slice(),
+                   "<struct><member><name>id</name><value><int>7</int></value></member></struct>");
+        let options = DecoderOptions { transparent_newtypes: true, ..Default::default() };
+        assert_eq!(decode_with::<Named>(encoded.as_slice(), Default::default(), options).unwrap(),
+                   named);
+    }
+
+    #[derive(RustcEncodable, RustcDecodable, PartialEq, Show)]
+    struct Marker;
+
+    #[test]
+    fn test_unit_policy() {
+        assert_eq!(encode(&()).as_slice(), "<nil/>");
+        assert_eq!(encode(&Marker).as_slice(), "<nil/>");
+        let options = EncoderOptions { units: Units::EmptyStruct, ..Default::default() };
+        assert_eq!(encode_with(&Marker, options).as_slice(), "<struct></struct>");
+        let options = EncoderOptions { units: Units::EmptyString, ..Default::default() };
+        assert_eq!(encode_with(&(), options).as_slice(), "<string></string>");
+        assert_eq!(encode_with(&None::<i32>, options).as_slice(), "<nil/>");
+        for s in ["<nil/>", "<struct></struct>", "<string></string>"].iter() {
+            assert_eq!(decode::<()>(*s).unwrap(), ());
+            assert_eq!(decode::<Marker>(*s).unwrap(), Marker);
+        }
+    }
+
+    #[test]
+    fn test_members_keep_order_and_duplicates() {
+        let members = Members(vec![("z".to_string(), 1i32), ("a".to_string(), 2),
+                                   ("z".to_string(), 3)]);
+        assert_eq!(encode(&members).as_slice(),
+                   "<struct><member><name>z</name><value><int>1</int></value></member>\
+                    <member><name>a</name><value><int>2</int></value></member>\
+                    <member><name>z</name><value><int>3</int></value></member></struct>");
+        let decoded: Members<i32> = decode("<struct><member><name>b</name><value><int>1</int></value></member>\
+                                            <member><name>a</name><value><int>2</int></value></member></struct>").unwrap();
+        assert_eq!(decoded, Members(vec![("a".to_string(), 2), ("b".to_string(), 1)]));
+    }
+
+    #[test]
+    fn test_array_from_iter() {
+        let squares = array_from_iter(range(1i32, 4).map(|i| i * i));
+        assert_eq!(encode(&squares).as_slice(),
+                   "<array><data><value><int>1</int></value><value><int>4</int></value>\
+                    <value><int>9</int></value></data></array>");
+        assert_eq!(encode(&squares).as_slice(), "<array><data></data></array>");
+
+        let mut s = string::String::new();
+        {
+            let mut encoder = Encoder::new(&mut s);
+            encoder.emit_array_from_iter(vec!["a", "b"].into_iter()).unwrap();
+        }
+        assert_eq!(s.as_slice(),
+                   "<array><data><value><string>a</string></value>\
+                    <value><string>b</string></value></data></array>");
+    }
+
+    #[test]
+    fn test_value_writer() {
+        let mut s = string::String::new();
+        {
+            let mut w = ValueWriter::new(&mut s);
+            w.begin_struct().unwrap();
+            w.member("a<b", |w| w.string("x & y")).unwrap();
+            w.member("list", |w| {
+                try!(w.begin_array());
+                try!(w.element(|w| w.int(1)));
+                try!(w.element(|w| w.value(&Some(true))));
+                w.end_array()
+            }).unwrap();
+            w.end_struct().unwrap();
+        }
+        assert_eq!(s.as_slice(),
+                   "<struct><member><name>a&lt;b</name><value><string>x &amp; y</string></value></member>\
+                    <member><name>list</name><value><array><data><value><int>1</int></value>\
+                    <value><boolean>1</boolean></value></data></array></value></member></struct>");
+    }
+
+    #[test]
+    fn test_char_round_trip() {
+        for &c in ['a', '\u{e9}', '\u{4e2d}', '\u{1f600}'].iter() {
+            let decoded: char = decode(encode(&c).as_slice()).unwrap();
+            assert_eq!(decoded, c);
+        }
+        let options = EncoderOptions { ascii_only: true, ..Default::default() };
+        assert_eq!(encode_with(&'\u{1f600}', options).as_slice(), "<string>&#x1F600;</string>");
+    }
+
+    #[test]
+    fn test_decode_char_rejects_other_lengths() {
+        let empty: Result<char, _> = decode("<string></string>");
+        assert!(empty.is_err());
+        let combining: Result<char, _> = decode("<string>e\u{301}</string>");
+        assert!(combining.is_err());
+    }
+
+    #[test]
+    fn test_large_integers() {
+        let big = 1u64 << 40;
+        let options = EncoderOptions { large_integers: LargeIntegers::String, ..Default::default() };
+        let encoded = encode_with(&big, options);
+        assert_eq!(encoded.as_slice(), "<string>1099511627776</string>");
+        assert_eq!(decode::<u64>(encoded.as_slice()).unwrap(), big);
+
+        let options = EncoderOptions { large_integers: LargeIntegers::Double, ..Default::default() };
+        let encoded = encode_with(&big, options);
+        assert!(decode::<u64>(encoded.as_slice()).is_err());
+        let options = DecoderOptions { large_integers: LargeIntegers::Double, ..Default::default() };
+        assert_eq!(decode_with::<u64>(encoded.as_slice(), Default::default(), options).unwrap(), big);
+
+        assert_eq!(encode(&7u64).as_slice(), "<int>7</int>");
+
+        // <ex:i8> cannot carry values beyond i64, which follow the strategy
+        let options = EncoderOptions { large_integers: LargeIntegers::String,
+                                       ..Profile::Apache.encoder_options() };
+        assert_eq!(encode_with(&u64::MAX, options).as_slice(), "<string>18446744073709551615</string>");
+    }
+
+    #[test]
+    fn test_large_integers_to_xml() {
+        assert_eq!((1u64 << 40).to_xml(), Xml::String("1099511627776".to_string()));
+        assert_eq!(from_value::<u64>(&(1u64 << 40).to_xml()).unwrap(), 1u64 << 40);
+        assert_eq!((-1i64 << 40).to_xml(), Xml::String("-1099511627776".to_string()));
+        assert_eq!(u32::MAX.to_xml(), Xml::String("4294967295".to_string()));
+        assert_eq!((-5i64).to_xml(), Xml::I32(-5));
+        assert_eq!(7usize.to_xml(), Xml::I32(7));
+    }
+
+    #[test]
+    fn test_from_bytes_skips_bom() {
+        let xml = Xml::from_bytes(b"\xEF\xBB\xBF<int>5</int>").unwrap();
+        assert_eq!(xml, Xml::I32(5));
+        assert_eq!(Xml::from_bytes(b"<int>5</int>").unwrap(), Xml::I32(5));
+    }
+
+    #[test]
+    fn test_parse_without_params() {
+        let call = "<methodCall><methodName>system.listMethods</methodName></methodCall>";
+        assert_eq!(parse_method_call(call, Default::default()).unwrap(),
+                   ("system.listMethods".to_string(), vec![]));
+        let response = "<methodResponse></methodResponse>";
+        assert_eq!(parse_method_response(response, Default::default()).unwrap(), Ok(vec![]));
+        let response = "<methodResponse><params></params></methodResponse>";
+        assert_eq!(parse_method_response(response, Default::default()).unwrap(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_decode_nested_arrays_of_tuples() {
+        // the shape of a ROS master getSystemState result
+        let state = vec![
+            vec![("/chatter".to_string(), vec!["/talker".to_string()]),
+                 ("/rosout".to_string(), vec!["/talker".to_string(), "/listener".to_string()])],
+            vec![],
+            vec![("/rosout_agg".to_string(), vec![])],
+        ];
+        let decoded: Vec<Vec<(string::String, Vec<string::String>)>> =
+            decode(encode(&state).as_slice()).unwrap();
+        assert_eq!(decoded, state);
+    }
+
+    #[test]
+    fn test_decode_truncated_array() {
+        let truncated: Result<Vec<i32>, _> = decode("<array><data><value><int>1</int></value>");
+        assert!(truncated.is_err());
+    }
+
+    #[test]
+    fn test_malformed_input_does_not_panic() {
+        let inputs = [
+            "", "<", "<value>", "<int>", "<int>1", "<int>1</double>", "<int>99999999999</int>",
+            "<struct><member>", "<struct><member><name>a</name></member></struct>",
+            "<array><data><value>", "<array><int>1</int></array>", "</array>",
+            "<dateTime.iso8601>2015</dateTime.iso8601>", "<int>1</int><int>2</int>",
+            "<methodCall>", "<methodCall><methodName></methodName>",
+            "<methodResponse><fault></fault></methodResponse>",
+            "<methodResponse><params><param>",
+        ];
+        for input in inputs.iter() {
+            let _ = Xml::from_str(*input);
+            let _ = parse_method_call(*input, Default::default());
+            let _ = parse_method_response(*input, Default::default());
+            let _: Result<Vec<(i32, string::String)>, _> = decode(*input);
+            let _: Result<Optional, _> = decode(*input);
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |depth: usize| {
+            let mut s = string::String::new();
+            for _ in range(0, depth) { s.push_str("<array><data><value>"); }
+            s.push_str("<int>1</int>");
+            for _ in range(0, depth) { s.push_str("</value></data></array>"); }
+            s
+        };
+        assert!(Xml::from_str(nested(DEFAULT_MAX_DEPTH).as_slice()).is_ok());
+        assert_eq!(Xml::from_str(nested(DEFAULT_MAX_DEPTH + 1).as_slice()),
+                   Err(SyntaxError(NestingTooDeep, 0, 0)));
+        let options = ParserOptions { max_depth: 2, ..Default::default() };
+        assert!(Xml::from_str_with(nested(3).as_slice(), options).is_err());
+
+        // fails at the limit rather than overflowing the stack
+        assert!(Xml::from_str(nested(100000).as_slice()).is_err());
+    }
+
+    // parses `s`, returning the value with the deviations accepted
+    fn build_with(s: &str, options: ParserOptions) -> (Result<Xml, ParserError>, Vec<Warning>) {
+        let mut builder = Builder::new_with(BufReader::new(s.as_bytes()), options);
+        let value = builder.build();
+        (value, builder.warnings())
+    }
+
+    #[test]
+    fn test_lenient_numbers() {
+        let lenient = ParserOptions { lenient_numbers: true, ..Default::default() };
+        assert!(Xml::from_str("<int>+5</int>").is_err());
+        assert_eq!(build_with("<int>+5</int>", lenient),
+                   (Ok(Xml::I32(5)), vec![Warning::LenientNumber("+5".to_string())]));
+        assert!(Xml::from_str("<double>1e3</double>").is_err());
+        assert_eq!(build_with("<double>+1.5E-1</double>", lenient),
+                   (Ok(Xml::F64(0.15)), vec![Warning::LenientNumber("+1.5E-1".to_string())]));
+        assert_eq!(build_with("<double>-2.5</double>", lenient), (Ok(Xml::F64(-2.5)), vec![]));
+
+        for s in ["<int>+-5</int>", "<int>++5</int>", "<int>+</int>", "<double>inf</double>",
+                  "<double>NaN</double>", "<double>1e999</double>", "<double>+-1</double>",
+                  "<double>1e</double>", "<double>.</double>"].iter() {
+            let (value, warnings) = build_with(*s, lenient);
+            assert!(value.is_err(), "{} parsed", s);
+            assert!(warnings.is_empty(), "{} warned", s);
+        }
+    }
+
+    #[test]
+    fn test_lenient_booleans() {
+        let lenient = ParserOptions { lenient_booleans: true, ..Default::default() };
+        for s in ["true", "FALSE"].iter() {
+            let doc = format!("<boolean>{}</boolean>", s);
+            assert!(Xml::from_str(doc.as_slice()).is_err());
+            assert_eq!(build_with(doc.as_slice(), lenient),
+                       (Ok(Xml::Boolean(*s == "true")), vec![Warning::LenientBoolean(s.to_string())]));
+        }
+        assert_eq!(build_with("<boolean>1</boolean>", lenient), (Ok(Xml::Boolean(true)), vec![]));
+        assert!(Xml::from_str_with("<boolean>yes</boolean>", lenient).is_err());
+    }
+
+    #[test]
+    fn test_trim_scalars() {
+        let trim = ParserOptions { trim_scalars: true, ..Default::default() };
+        assert!(Xml::from_str("<int> 5 </int>").is_err());
+        assert_eq!(build_with("<int> 5 </int>", trim),
+                   (Ok(Xml::I32(5)), vec![Warning::TrimmedScalar(" 5 ".to_string())]));
+        assert_eq!(build_with("<double>\n1.5\t</double>", trim).0, Ok(Xml::F64(1.5)));
+        // strings are kept as sent, whether or not scalars are trimmed
+        assert_eq!(build_with("<string> a </string>", trim),
+                   (Ok(Xml::String(" a ".to_string())), vec![]));
+        assert_eq!(Xml::from_str("<string> a </string>"), Ok(Xml::String(" a ".to_string())));
+    }
+
+    #[test]
+    fn test_builder_warnings() {
+        let doc = "<array><data><comment>from <b>v1</b></comment>\
+                   <value><int> +1 </int></value><value><boolean>true</boolean></value></data></array>";
+        assert!(Xml::from_str(doc).is_err());
+        let (value, warnings) = build_with(doc, ParserOptions::lenient());
+        assert_eq!(value, Ok(Xml::Array(vec![Xml::I32(1), Xml::Boolean(true)])));
+        assert_eq!(warnings, vec![Warning::SkippedElement("comment".to_string()),
+                                  Warning::TrimmedScalar(" +1 ".to_string()),
+                                  Warning::LenientNumber("+1".to_string()),
+                                  Warning::LenientBoolean("true".to_string())]);
+    }
+
+    #[test]
+    fn test_decoder_warnings() {
+        let value = Xml::Array(vec![Xml::String("5".to_string()), Xml::I32(6)]);
+        let mut decoder = Decoder::new(value);
+        let decoded: Vec<i32> = Decodable::decode(&mut decoder).unwrap();
+        assert_eq!(decoded, vec![5, 6]);
+        assert_eq!(decoder.warnings(), [Warning::Coerced("value[0]".to_string(), "string", "integer")]);
+
+        let mut decoder = Decoder::new(Xml::String("2.5".to_string()));
+        assert_eq!(Decodable::decode(&mut decoder), Ok(2.5f64));
+        assert_eq!(decoder.warnings(), [Warning::Coerced("value".to_string(), "string", "double")]);
+
+        // integers beyond <int> and map keys are strings by design
+        let mut decoder = Decoder::new(Xml::String("4294967296".to_string()));
+        assert_eq!(Decodable::decode(&mut decoder), Ok(4294967296u64));
+        assert!(decoder.warnings().is_empty());
+        let mut members = BTreeMap::new();
+        members.insert("7".to_string(), Xml::I32(1));
+        let mut decoder = Decoder::new(Xml::Object(members));
+        let decoded: HashMap<i32, i32> = Decodable::decode(&mut decoder).unwrap();
+        assert_eq!(decoded[7], 1);
+        assert!(decoder.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_decode_renamed_members() {
+        #[derive(RustcDecodable, PartialEq, Show)]
+        struct Status { fault_code: i32, content_type: string::String }
+
+        let xml = "<struct><member><name>faultCode</name><value><int>4</int></value></member>\
+                   <member><name>content-type</name><value><string>text/xml</string></value></member>\
+                   </struct>";
+        assert!(decode::<Status>(xml).is_err());
+        let options = DecoderOptions::default()
+            .rename("fault_code", "faultCode")
+            .rename("content_type", "content-type");
+        let status: Status = decode_with(xml, Default::default(), options.clone()).unwrap();
+        assert_eq!(status, Status { fault_code: 4, content_type: "text/xml".to_string() });
+
+        let xml = "<struct><member><name>FAULTCODE</name><value><int>4</int></value></member>\
+                   <member><name>Content-Type</name><value><string>text/xml</string></value></member>\
+                   </struct>";
+        assert!(decode_with::<Status>(xml, Default::default(), options.clone()).is_err());
+        let options = DecoderOptions { case_insensitive: true, ..options };
+        let status: Status = decode_with(xml, Default::default(), options).unwrap();
+        assert_eq!(status.fault_code, 4);
+    }
+
+    #[test]
+    fn test_unknown_members() {
+        #[derive(RustcDecodable, PartialEq, Show)]
+        struct Node { pid: i32 }
+
+        let xml = "<struct><member><name>pid</name><value><int>7</int></value></member>\
+                   <member><name>host</name><value><string>a</string></value></member></struct>";
+        assert_eq!(decode::<Node>(xml), Ok(Node { pid: 7 }));
+
+        let options = DecoderOptions { unknown_members: UnknownMembers::Deny, ..Default::default() };
+        assert_eq!(decode_with::<Node>(xml, Default::default(), options),
+                   Err(DecoderError::UnknownMemberError("host".to_string())));
+
+        let options = DecoderOptions { unknown_members: UnknownMembers::Collect, ..Default::default() };
+        let mut decoder = Decoder::new_with(Xml::from_str(xml).unwrap(), options);
+        assert_eq!(Decodable::decode(&mut decoder), Ok(Node { pid: 7 }));
+        let mut extra = BTreeMap::new();
+        extra.insert("host".to_string(), Xml::String("a".to_string()));
+        assert_eq!(decoder.unknown_members(), [extra]);
+    }
+
+    #[test]
+    fn test_decode_subtree() {
+        #[derive(RustcDecodable, PartialEq, Show)]
+        struct Node { pid: i32, name: string::String }
+
+        let xml = Xml::from_str("<struct><member><name>status</name><value><struct>\
+            <member><name>node</name><value><struct>\
+            <member><name>pid</name><value><int>7</int></value></member>\
+            <member><name>name</name><value><string>talker</string></value></member>\
+            </struct></value></member></struct></value></member>\
+            <member><name>code</name><value><int>1</int></value></member></struct>").unwrap();
+        let subtree = xml.find_path(&["status", "node"]).unwrap();
+        let node: Node = subtree.decode_into().unwrap();
+        assert_eq!(node, Node { pid: 7, name: "talker".to_string() });
+        assert_eq!(from_value::<Node>(subtree), Ok(node));
+        // the tree is left as it was
+        assert_eq!(xml.find_path(&["status", "node", "pid"]), Some(&Xml::I32(7)));
+        assert!(xml.find("code").unwrap().decode_into::<Node>().is_err());
+    }
+
+    #[test]
+    fn test_decode_error_path() {
+        #[derive(RustcDecodable, Show)]
+        struct Item { name: string::String }
+        #[derive(RustcDecodable, Show)]
+        struct Group { list: Vec<Item> }
+
+        let item = |name: &str| format!("<value><struct><member><name>name</name>\
+                                         <value>{}</value></member></struct></value>", name);
+        let xml = format!("<array><data><value><struct><member><name>list</name><value><array><data>\
+                           {}{}{}</data></array></value></member></struct></value></data></array>",
+                          item("<string>a</string>"), item("<string>b</string>"), item("<int>3</int>"));
+        match decode::<Vec<Group>>(xml.as_slice()) {
+            Err(DecoderError::PathError(path, e)) => {
+                assert_eq!(path.as_slice(), "value[0].list[2].name");
+                assert!(match *e { DecoderError::ExpectedError(ref t, _) => t.as_slice() == "String",
+                                   _ => false });
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
 
+    #[test]
+    fn test_from_str_trait() {
+        let parsed: Option<Xml> = "<array><data><value><int>5</int></value></data></array>".parse();
+        assert_eq!(parsed, Some(Xml::Array(vec![Xml::I32(5)])));
+        let parsed: Option<Xml> = "<int>5</int><int>6</int>".parse();
+        assert_eq!(parsed, None);
+        assert_eq!("<int>five</int>".parse::<Xml>(), None);
+    }
+
+    #[test]
+    fn test_builder_reset() {
+        let options = ParserOptions { max_depth: 2, ..ParserOptions::lenient() };
+        let nested = "<array><data><value><array><data></data></array></value></data></array>";
+        let mut builder = Builder::new_with(BufReader::new(b"<array><data><value><array><data>\
+            <value><int> +x </int></value></data></array></value></data></array>"), options);
+        assert!(builder.build().is_err());
+        assert!(!builder.warnings().is_empty());
+
+        builder.reset(BufReader::new(nested.as_bytes()));
+        assert!(builder.warnings().is_empty());
+        assert_eq!(builder.build(), Ok(Xml::Array(vec![Xml::Array(vec![])])));
+        builder.reset(BufReader::new(b"<boolean>true</boolean>"));
+        assert_eq!(builder.build(), Ok(Xml::Boolean(true)));
+        assert_eq!(builder.warnings(), vec![Warning::LenientBoolean("true".to_string())]);
+    }
+
+    #[test]
+    fn test_reader_config() {
+        let trim = ParserOptions { trim_whitespace: true, ..Default::default() };
+        assert_eq!(Xml::from_str("<string>  </string>"), Ok(Xml::String("  ".to_string())));
+        assert_eq!(build_with("<string>  </string>", trim).0, Ok(Xml::String("".to_string())));
+
+        let cdata = "<string><![CDATA[a < b]]></string>";
+        assert_eq!(Xml::from_str(cdata), Ok(Xml::String("a < b".to_string())));
+        let strict = ParserOptions { cdata_to_characters: false, ..Default::default() };
+        assert!(build_with(cdata, strict).0.is_err());
+    }
+
+    #[test]
+    fn test_escape_str() {
+        assert_eq!(encode(&"plain").as_slice(), "<string>plain</string>");
+        assert_eq!(encode(&"a<b & 'c' > \"d\"").as_slice(),
+                   "<string>a&lt;b &amp; &apos;c&apos; &gt; &quot;d&quot;</string>");
+        let options = EncoderOptions { ascii_only: true, ..Default::default() };
+        assert_eq!(encode_with(&"caf\u{e9} & co", options).as_slice(),
+                   "<string>caf&#xE9; &amp; co</string>");
+        assert_eq!(encode_with(&"\u{1F600}<", options).as_slice(),
+                   "<string>&#x1F600;&lt;</string>");
+        assert_eq!(encode(&"caf\u{e9} & co").as_slice(), "<string>caf\u{e9} &amp; co</string>");
+    }
+
+    #[test]
+    fn test_int_tag() {
+        let options = EncoderOptions { int_tag: IntTag::I4, ..Default::default() };
+        assert_eq!(encode_with(&42i32, options).as_slice(), "<i4>42</i4>");
+        assert_eq!(decode::<i32>("<i4>42</i4>").unwrap(), 42);
+    }
+
+    #[test]
+    fn test_escape_member_names() {
+        let mut map = BTreeMap::new();
+        map.insert("a<b&c".to_string(), 1i32);
+        let encoded = encode(&map);
+        assert_eq!(encoded.as_slice(),
+                   "<struct><member><name>a&lt;b&amp;c</name><value><int>1</int></value></member></struct>");
+        let decoded: BTreeMap<string::String, i32> = decode(encoded.as_slice()).unwrap();
+        assert_eq!(decoded, map);
+        assert_eq!(format!("{}", Xml::Object(BTreeMap::new())).as_slice(), "<struct></struct>");
+    }
+
+    #[test]
+    fn test_apache_extensions() {
+        let options = Profile::Apache.parser_options();
+        let ns = "xmlns:ex=\"http://ws.apache.org/xmlrpc/namespaces/extensions\"";
+        let i8 = format!("<ex:i8 {}>4294967296</ex:i8>", ns);
+        let decoded: i64 = decode_with(i8.as_slice(), options, Default::default()).unwrap();
+        assert_eq!(decoded, 4294967296);
+        let nil = format!("<ex:nil {}/>", ns);
+        assert_eq!(Xml::from_str_with(nil.as_slice(), options).unwrap(), Xml::Null);
+        assert!(Xml::from_str(i8.as_slice()).is_err());
+
+        let encoded = encode_with(&(1i64 << 32), Profile::Apache.encoder_options());
+        assert_eq!(encoded, format!("<ex:i8 {}>4294967296</ex:i8>", ns));
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        let value = Xml::from_str("<base64>AAEC/w==</base64>").unwrap();
+        assert_eq!(value, Xml::Base64(vec![0, 1, 2, 255]));
+        let encoded = encode(&value);
+        assert_eq!(encoded.as_slice(), "<base64>AAEC/w==</base64>");
+        assert_eq!(Xml::from_str(encoded.as_slice()).unwrap(), value);
+
+        // Apache's serializable values keep their bytes, written back as base64
+        let options = Profile::Apache.parser_options();
+        let ns = "xmlns:ex=\"http://ws.apache.org/xmlrpc/namespaces/extensions\"";
+        let serializable = format!("<ex:serializable {}>AAEC/w==</ex:serializable>", ns);
+        let value = Xml::from_str_with(serializable.as_slice(), options).unwrap();
+        let encoded = encode_with(&value, Profile::Apache.encoder_options());
+        assert_eq!(Xml::from_str_with(encoded.as_slice(), options).unwrap(), value);
+    }
+
+    // documents as produced by Python's xmlrpc.client.dumps
+    const PYTHON_CALL: &'static str = "<?xml version='1.0'?>\n<methodCall>\n\
+        <methodName>examples.echo</methodName>\n<params>\n\
+        <param>\n<value><int>1</int></value>\n</param>\n\
+        <param>\n<value><string>a &amp; b</string></value>\n</param>\n\
+        <param>\n<value><nil/></value>\n</param>\n\
+        <param>\n<value><dateTime.iso8601>20150102T03:04:05</dateTime.iso8601></value>\n</param>\n\
+        <param>\n<value><double>1e+100</double></value>\n</param>\n\
+        </params>\n</methodCall>\n";
+    const PYTHON_FAULT: &'static str = "<?xml version='1.0'?>\n<methodResponse>\n<fault>\n\
+        <value><struct>\n<member>\n<name>faultCode</name>\n<value><int>1</int></value>\n</member>\n\
+        <member>\n<name>faultString</name>\n<value><string>boom</string></value>\n</member>\n\
+        </struct></value>\n</fault>\n</methodResponse>\n";
+
+    #[test]
+    fn test_python_interop() {
+        let options = Profile::Python.parser_options();
+        let (method, params) = parse_method_call(PYTHON_CALL, options).unwrap();
+        assert_eq!(method.as_slice(), "examples.echo");
+        assert_eq!(params[0], Xml::I32(1));
+        assert_eq!(params[1], Xml::String("a & b".to_string()));
+        assert_eq!(params[2], Xml::Null);
+        assert!(params[3].is_datetime());
+        assert_eq!(params[4], Xml::F64(1e100));
+
+        let fault = parse_method_response(PYTHON_FAULT, options).unwrap().unwrap_err();
+        assert_eq!(fault.find("faultCode"), Some(&Xml::I32(1)));
+
+        // older marshallers write untyped and empty values
+        assert_eq!(Xml::from_str("<array><data><value>x</value><value></value></data></array>"),
+                   Ok(Xml::Array(vec![Xml::String("x".to_string()), Xml::String("".to_string())])));
+        assert_eq!(decode::<i32>("<i4>7</i4>").unwrap(), 7);
+    }
+
+    #[test]
+    fn test_redact() {
+        let value = Xml::from_str("<struct>\
+            <member><name>user</name><value>alice</value></member>\
+            <member><name>password</name><value>hunter2</value></member>\
+            <member><name>nested</name><value><array><data><value><struct>\
+              <member><name>token</name><value><int>42</int></value></member>\
+            </struct></value></data></array></value></member></struct>").unwrap();
+        let redacted = value.redact(&["password", "token"]);
+        assert_eq!(redacted.find("user"), Some(&Xml::String("alice".to_string())));
+        assert_eq!(redacted.find("password"), Some(&Xml::String("***".to_string())));
+        let nested = redacted.find("nested").unwrap().as_array().unwrap();
+        assert_eq!(nested[0].find("token"), Some(&Xml::String("***".to_string())));
+        // the original is untouched
+        assert_eq!(value.find("password"), Some(&Xml::String("hunter2".to_string())));
+    }
+
+    #[test]
+    fn test_stats() {
+        let value = Xml::from_str("<struct>\
+            <member><name>ab</name><value><array><data>\
+              <value><int>1</int></value><value><double>2.5</double></value>\
+              <value><base64>AAEC</base64></value><value><nil/></value>\
+            </data></array></value></member>\
+            <member><name>c</name><value><string>xyz</string></value></member>\
+            <member><name>d</name><value><boolean>1</boolean></value></member></struct>").unwrap();
+        let stats = value.stats();
+        assert_eq!((stats.structs, stats.arrays), (1, 1));
+        assert_eq!((stats.ints, stats.doubles, stats.base64s, stats.nils), (1, 1, 1, 1));
+        assert_eq!((stats.strings, stats.booleans, stats.datetimes), (1, 1, 0));
+        assert_eq!(stats.max_depth, 3);
+        // member names "ab", "c" and "d" and the string "xyz"
+        assert_eq!(stats.string_bytes, 7);
+        assert_eq!(stats.base64_bytes, 3);
+        assert_eq!(Xml::I32(1).stats().max_depth, 1);
+    }
 }
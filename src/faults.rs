@@ -0,0 +1,109 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use rustc_serialize::Decodable;
+
+use protocol::{Fault, Response, ResponseError};
+
+/// The errors that can arise from a response read through a `FaultMap`.
+#[derive(Clone, PartialEq, Show)]
+pub enum MappedError<E> {
+    /// A fault matched by one of the map's rules
+    Mapped(E),
+    /// Any other failure, including faults no rule matched
+    Response(ResponseError),
+}
+
+struct Rule<E> {
+    code: Option<i32>,
+    matches: Box<Fn(&str) -> bool + 'static>,
+    convert: Box<Fn(&Fault) -> E + 'static>,
+}
+
+/// Rules turning faults into an application's own error type, so callers
+/// match on typed errors rather than fault strings. Rules are tried in the
+/// order they were added.
+pub struct FaultMap<E> {
+    rules: Vec<Rule<E>>,
+}
+
+impl<E> FaultMap<E> {
+    pub fn new() -> FaultMap<E> {
+        FaultMap { rules: Vec::new() }
+    }
+
+    /// Maps every fault with `code` through `convert`.
+    pub fn code<F>(self, code: i32, convert: F) -> FaultMap<E> where
+        F: Fn(&Fault) -> E + 'static,
+    {
+        self.rule(Some(code), |_: &str| true, convert)
+    }
+
+    /// Maps faults whose code is `code`, or any code if None, and whose
+    /// string satisfies `matches`, through `convert`.
+    pub fn rule<M, F>(mut self, code: Option<i32>, matches: M, convert: F) -> FaultMap<E> where
+        M: Fn(&str) -> bool + 'static,
+        F: Fn(&Fault) -> E + 'static,
+    {
+        self.rules.push(Rule { code: code, matches: Box::new(matches), convert: Box::new(convert) });
+        self
+    }
+
+    /// Returns the error the first matching rule makes of `fault`.
+    pub fn map(&self, fault: &Fault) -> Option<E> {
+        self.rules.iter()
+            .find(|rule| {
+                rule.code.map_or(true, |code| code == fault.code) &&
+                    (*rule.matches)(fault.string.as_slice())
+            })
+            .map(|rule| (*rule.convert)(fault))
+    }
+
+    /// Decodes the parameter at `idx` of `response`, mapping a fault
+    /// through the rules.
+    pub fn result<T: Decodable>(&self, response: &Response, idx: usize)
+                                -> Result<T, MappedError<E>> {
+        match response.result(idx) {
+            Ok(value) => Ok(value),
+            Err(ResponseError::Fault(fault)) => match self.map(&fault) {
+                Some(e) => Err(MappedError::Mapped(e)),
+                None => Err(MappedError::Response(ResponseError::Fault(fault))),
+            },
+            Err(e) => Err(MappedError::Response(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FaultMap, MappedError};
+    use fixtures;
+    use protocol::Response;
+    use std::string;
+
+    #[derive(PartialEq, Show)]
+    enum AppError { NotFound, Locked(string::String) }
+
+    #[test]
+    fn test_fault_map() {
+        let faults = FaultMap::new()
+            .code(404, |_| AppError::NotFound)
+            .rule(None, |s: &str| s.starts_with("locked:"), |f| AppError::Locked(f.string.clone()));
+        let not_found = Response::new(fixtures::fault(404, "no such user").as_slice());
+        assert_eq!(faults.result::<i32>(&not_found, 0), Err(MappedError::Mapped(AppError::NotFound)));
+        let locked = Response::new(fixtures::fault(1, "locked: by admin").as_slice());
+        assert_eq!(faults.result::<i32>(&locked, 0),
+                   Err(MappedError::Mapped(AppError::Locked("locked: by admin".to_string()))));
+        let other = Response::new(fixtures::fault(2, "boom").as_slice());
+        assert!(match faults.result::<i32>(&other, 0) { Err(MappedError::Response(_)) => true, _ => false });
+        let ok = Response::new(fixtures::response_ok(&5i32).as_slice());
+        assert_eq!(faults.result::<i32>(&ok, 0), Ok(5));
+    }
+}
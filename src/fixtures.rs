@@ -0,0 +1,71 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+//! Canned methodCall and methodResponse documents, for tests which stand
+//! in for a server or client.
+
+use std::string;
+
+use rustc_serialize::Encodable;
+
+use encoding::{encode, Xml};
+use protocol::{Declaration, Fault, Request, Response};
+
+/// Returns a methodResponse carrying `value` as its single parameter.
+pub fn response_ok<T: Encodable>(value: &T) -> string::String {
+    Response::success(value, Declaration::Version).body
+}
+
+/// Returns a methodResponse carrying each of `values` as a parameter.
+pub fn response_values(values: &[Xml]) -> string::String {
+    let mut body = format!("{}<methodResponse><params>", Declaration::Version.as_str());
+    for value in values.iter() {
+        body.push_str(format!("\n<param>\n<value>{}</value>\n</param>", encode(value)).as_slice());
+    }
+    body.push_str("\n</params></methodResponse>");
+    body
+}
+
+/// Returns a methodResponse carrying a fault with `code` and `message`.
+pub fn fault(code: i32, message: &str) -> string::String {
+    fault_with(&Fault::new(code, message))
+}
+
+/// Returns a methodResponse carrying `fault`, extra members included.
+pub fn fault_with(fault: &Fault) -> string::String {
+    Response::failure(fault, Declaration::Version).body
+}
+
+/// Returns a methodCall of `method` with each of `params` as a parameter.
+pub fn call(method: &str, params: &[Xml]) -> string::String {
+    Request::from_xml(method, params).document()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{call, fault, response_ok, response_values};
+    use encoding::Xml;
+    use protocol::{Request, Response};
+    use std::string;
+
+    #[test]
+    fn test_fixtures_parse_back() {
+        let response = Response::new(response_ok(&"South Dakota").as_slice());
+        assert_eq!(response.result::<string::String>(0).unwrap(), "South Dakota");
+        let values = [Xml::I32(1), Xml::String("two".to_string())];
+        let response = Response::new(response_values(&values).as_slice());
+        assert_eq!(response.into_values().unwrap(), values.to_vec());
+        let response = Response::new(fault(4, "Too many parameters.").as_slice());
+        let f = response.fault().unwrap();
+        assert_eq!((f.code, f.string.as_slice()), (4, "Too many parameters."));
+        let request = Request::parse(call("examples.getStateName", &[Xml::I32(41)]).as_slice());
+        assert_eq!(request.unwrap().params().unwrap(), vec![Xml::I32(41)]);
+    }
+}
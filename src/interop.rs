@@ -0,0 +1,280 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+//! Analysis of captured request and response bodies, reporting how each
+//! peer departs from the spec and which options interoperate with it.
+
+use std::collections::BTreeMap;
+use std::default::Default;
+use std::io;
+use std::io::fs;
+use std::io::fs::PathExtensions;
+use std::io::{File, IoResult};
+use std::str;
+use std::string;
+
+use xml::EventReader;
+use xml::reader::ParserConfig;
+use xml::reader::events::XmlEvent;
+
+use encoding::{Builder, EncoderOptions, IntTag, ParserOptions, Warning, APACHE_EXTENSIONS_NS};
+
+/// A departure from the spec observed in a captured body.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Show)]
+pub enum Deviation {
+    /// The body begins with a UTF-8 byte order mark
+    ByteOrderMark,
+    /// `<nil/>`, the common extension for None
+    Nil,
+    /// Apache's `ex:` extension types
+    ApacheExtensions,
+    /// Integers written as `<i4>`, which is allowed but worth matching
+    I4Tag,
+    /// Numbers with a leading '+' or in scientific notation
+    LenientNumbers,
+    /// Booleans written as "true" or "false"
+    LenientBooleans,
+    /// Scalars padded with whitespace
+    PaddedScalars,
+    /// CDATA sections
+    Cdata,
+    /// Elements outside the spec and its supported extensions
+    UnknownElements,
+    /// The body is not UTF-8 or does not parse even with every deviation allowed
+    Unparseable,
+}
+
+/// What was observed of one peer.
+#[derive(Clone, PartialEq, Show)]
+pub struct PeerReport {
+    /// The number of bodies analyzed
+    pub documents: usize,
+    /// The number of bodies showing each deviation
+    pub deviations: BTreeMap<Deviation, usize>,
+}
+
+impl PeerReport {
+    fn new() -> PeerReport {
+        PeerReport { documents: 0, deviations: BTreeMap::new() }
+    }
+
+    /// Returns true if any body showed `deviation`.
+    pub fn saw(&self, deviation: Deviation) -> bool {
+        self.deviations.contains_key(&deviation)
+    }
+
+    /// Returns the parser options needed to read what the peer sends.
+    pub fn parser_options(&self) -> ParserOptions {
+        ParserOptions {
+            lenient_numbers: self.saw(Deviation::LenientNumbers),
+            lenient_booleans: self.saw(Deviation::LenientBooleans),
+            trim_scalars: self.saw(Deviation::PaddedScalars),
+            apache_extensions: self.saw(Deviation::ApacheExtensions),
+            skip_unknown_elements: self.saw(Deviation::UnknownElements),
+            ..Default::default()
+        }
+    }
+
+    /// Returns encoder options writing what the peer itself writes.
+    pub fn encoder_options(&self) -> EncoderOptions {
+        EncoderOptions {
+            apache_extensions: self.saw(Deviation::ApacheExtensions),
+            int_tag: if self.saw(Deviation::I4Tag) { IntTag::I4 } else { IntTag::Int },
+            ..Default::default()
+        }
+    }
+}
+
+/// Collects deviations from captured bodies, peer by peer.
+pub struct Analyzer {
+    peers: BTreeMap<string::String, PeerReport>,
+}
+
+impl Analyzer {
+    pub fn new() -> Analyzer {
+        Analyzer { peers: BTreeMap::new() }
+    }
+
+    /// Analyzes one captured body, a methodCall, methodResponse or value,
+    /// sent by `peer`.
+    pub fn add(&mut self, peer: &str, body: &[u8]) {
+        let found = deviations(body);
+        if !self.peers.contains_key(peer) {
+            self.peers.insert(peer.to_string(), PeerReport::new());
+        }
+        let report = self.peers.get_mut(peer).unwrap();
+        report.documents += 1;
+        for deviation in found.into_iter() {
+            let count = report.deviations.get(&deviation).map(|n| *n).unwrap_or(0);
+            report.deviations.insert(deviation, count + 1);
+        }
+    }
+
+    /// Analyzes every file under `dir`. Each subdirectory holds the bodies
+    /// of one peer, named after it; files directly in `dir` are attributed
+    /// to the peer "unknown".
+    pub fn add_dir(&mut self, dir: &Path) -> IoResult<()> {
+        for path in try!(fs::readdir(dir)).into_iter() {
+            if path.is_dir() {
+                let peer = path.filename_str().unwrap_or("unknown").to_string();
+                for file in try!(fs::walk_dir(&path)) {
+                    if file.is_file() {
+                        let body = try!(File::open(&file).read_to_end());
+                        self.add(peer.as_slice(), body.as_slice());
+                    }
+                }
+            } else if path.is_file() {
+                let body = try!(File::open(&path).read_to_end());
+                self.add("unknown", body.as_slice());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the report for each peer, by name.
+    pub fn reports(&self) -> &BTreeMap<string::String, PeerReport> {
+        &self.peers
+    }
+}
+
+const UTF8_BOM: &'static [u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Returns the deviations shown by one body.
+fn deviations(body: &[u8]) -> Vec<Deviation> {
+    let mut found = Vec::new();
+    let body = if body.starts_with(UTF8_BOM) {
+        found.push(Deviation::ByteOrderMark);
+        &body[UTF8_BOM.len()..]
+    } else {
+        body
+    };
+    let text = match str::from_utf8(body) {
+        Ok(text) => text,
+        Err(_) => {
+            found.push(Deviation::Unparseable);
+            return found;
+        }
+    };
+    found.extend(markup_deviations(text).into_iter());
+    match lenient_warnings(text) {
+        Some(warnings) => {
+            for warning in warnings.iter() {
+                let deviation = match *warning {
+                    Warning::LenientNumber(_) => Deviation::LenientNumbers,
+                    Warning::LenientBoolean(_) => Deviation::LenientBooleans,
+                    Warning::TrimmedScalar(_) => Deviation::PaddedScalars,
+                    Warning::SkippedElement(_) => Deviation::UnknownElements,
+                    Warning::Coerced(..) => continue,
+                };
+                push_new(&mut found, deviation);
+            }
+        }
+        None => found.push(Deviation::Unparseable),
+    }
+    found
+}
+
+/// Returns the deviations shown by the elements and CDATA sections of
+/// `text`, as opposed to its character data, up to any XML error.
+fn markup_deviations(text: &str) -> Vec<Deviation> {
+    let mut found = Vec::new();
+    let config = ParserConfig::new().cdata_to_characters(false);
+    let mut parser = EventReader::new_with_config(io::BufReader::new(text.as_bytes()), config);
+    loop {
+        let deviation = match parser.next() {
+            XmlEvent::StartElement { name, .. } => {
+                if name.namespace.as_ref().map(|ns| ns.as_slice()) == Some(APACHE_EXTENSIONS_NS) {
+                    if name.local_name.as_slice() == "nil" {
+                        push_new(&mut found, Deviation::Nil);
+                    }
+                    Deviation::ApacheExtensions
+                } else {
+                    match name.local_name.as_slice() {
+                        "nil" => Deviation::Nil,
+                        "i4" => Deviation::I4Tag,
+                        _ => continue,
+                    }
+                }
+            }
+            XmlEvent::CData(_) => Deviation::Cdata,
+            XmlEvent::EndDocument | XmlEvent::Error(_) => return found,
+            _ => continue,
+        };
+        push_new(&mut found, deviation);
+    }
+}
+
+fn push_new(found: &mut Vec<Deviation>, deviation: Deviation) {
+    if !found.contains(&deviation) {
+        found.push(deviation);
+    }
+}
+
+/// Parses `text` allowing every deviation, returning the warnings raised,
+/// or None if it does not parse as a call, a response or a value.
+fn lenient_warnings(text: &str) -> Option<Vec<Warning>> {
+    let options = ParserOptions { apache_extensions: true, ..ParserOptions::lenient() };
+    let reader = || io::BufReader::new(text.as_bytes());
+    let mut builder = Builder::new_with(reader(), options);
+    if builder.build_method_call().is_ok() {
+        return Some(builder.warnings());
+    }
+    let mut builder = Builder::new_with(reader(), options);
+    if builder.build_method_response().is_ok() {
+        return Some(builder.warnings());
+    }
+    let mut builder = Builder::new_with(reader(), options);
+    if builder.build().is_ok() {
+        return Some(builder.warnings());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analyzer, Deviation};
+
+    #[test]
+    fn test_deviations_come_from_markup() {
+        let mut analyzer = Analyzer::new();
+        analyzer.add("peer", b"<methodResponse><params><param><value><string>\
+            &lt;nil/&gt; ex:nil &lt;i4&gt; &lt;![CDATA[ http://ws.apache.org/xmlrpc/namespaces/extensions\
+            </string></value></param></params></methodResponse>");
+        let report = &analyzer.reports()["peer".to_string()];
+        assert!(report.deviations.is_empty());
+
+        analyzer.add("apache", b"<methodResponse xmlns:ex=\"http://ws.apache.org/xmlrpc/namespaces/extensions\">\
+            <params><param><value><array><data><value><ex:nil/></value><value><i4>1</i4></value>\
+            <value><string><![CDATA[a]]></string></value></data></array></value></param></params>\
+            </methodResponse>");
+        let report = &analyzer.reports()["apache".to_string()];
+        assert!(report.saw(Deviation::Nil) && report.saw(Deviation::ApacheExtensions));
+        assert!(report.saw(Deviation::I4Tag) && report.saw(Deviation::Cdata));
+    }
+
+    #[test]
+    fn test_interop_analyzer() {
+        let mut analyzer = Analyzer::new();
+        analyzer.add("python", b"<?xml version='1.0'?>\n<methodCall>\n\
+            <methodName>examples.echo</methodName>\n<params>\n\
+            <param>\n<value><nil/></value>\n</param>\n\
+            <param>\n<value><double>1e+100</double></value>\n</param>\n\
+            </params>\n</methodCall>\n");
+        analyzer.add("python", b"<methodResponse><params><param><value>\
+                                 <boolean>true</boolean></value></param></params></methodResponse>");
+        analyzer.add("broken", b"<methodResponse><params>");
+        let python = &analyzer.reports()["python".to_string()];
+        assert_eq!(python.documents, 2);
+        assert!(python.saw(Deviation::Nil) && python.saw(Deviation::LenientNumbers));
+        assert!(python.saw(Deviation::LenientBooleans) && !python.saw(Deviation::Unparseable));
+        assert!(python.parser_options().lenient_numbers);
+        assert!(analyzer.reports()["broken".to_string()].saw(Deviation::Unparseable));
+    }
+}
@@ -44,14 +44,67 @@ Additional errata and hints can be found here:
 
 extern crate "rustc-serialize" as rustc_serialize;
 extern crate xml;
+#[cfg(feature = "client")]
 extern crate hyper;
+extern crate time;
+#[cfg(feature = "logging")]
+#[macro_use]
+extern crate log;
+
+#[macro_use]
+mod macros;
+
+pub use encoding::{encode,encode_with,try_encode,try_encode_with,decode,decode_with,from_value};
+pub use encoding::{parse_method_call,parse_method_response,as_xml,array_from_iter};
+pub use encoding::{Xml,XmlStats,Members,ToXml,Encoder,ValueWriter,EncoderOptions,LargeIntegers,IntTag,Profile,Units};
+pub use encoding::{Builder,Decoder,DecoderOptions,ParserOptions,UnknownMembers,Warning};
+pub use encoding::{ParserError,DecoderError,EncoderError};
+pub use datetime::{DateTime};
+pub use protocol::{Request,Response,Declaration,MethodNames,Fault,ResponseError,ServerError,Params};
+pub use cache::{ResponseCache};
+pub use schema::{Signature,Type,SchemaError};
+pub use catalog::{MethodCatalog,MethodInfo,CatalogError};
+pub use faults::{FaultMap,MappedError};
+#[cfg(feature = "client")]
+pub use client::{Client,ClientBuilder,ClientStats,ConfigError,FailedCall,UrlError};
+#[cfg(feature = "client")]
+pub use proxy::{Proxy};
+#[cfg(feature = "client")]
+pub use breaker::{CircuitBreaker,MethodBreakers,BreakerState,BreakerError};
+#[cfg(feature = "client")]
+pub use dedup::{Deduplicator};
+#[cfg(feature = "client")]
+pub use limiter::{RateLimiter,TokenBucket};
+
+// the serialization traits, for the expansions of the exported macros in
+// crates which do not depend on rustc-serialize themselves
+#[doc(hidden)]
+pub mod serialize {
+    pub use rustc_serialize::{Encodable, Decodable, Encoder, Decoder};
+}
 
-pub use encoding::{encode,decode,Encoder,Decoder,Xml};
-pub use client::{Client};
-pub use protocol::{Request,Response};
 pub mod encoding;
-pub mod client;
+pub mod datetime;
 pub mod protocol;
+pub mod cache;
+pub mod schema;
+pub mod catalog;
+pub mod faults;
+pub mod signing;
+pub mod fixtures;
+pub mod conformance;
+pub mod interop;
+pub mod transport;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "client")]
+pub mod proxy;
+#[cfg(feature = "client")]
+pub mod breaker;
+#[cfg(feature = "client")]
+pub mod dedup;
+#[cfg(feature = "client")]
+pub mod limiter;
 #[cfg(test)]
 mod tests {
 
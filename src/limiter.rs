@@ -0,0 +1,164 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::timer;
+use std::string;
+use std::time::Duration;
+
+use time;
+
+/// A token bucket, refilled at a steady rate up to a burst size, with one
+/// token taken per call.
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: Cell<f64>,
+    refilled_at: Cell<u64>,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket allowing `rate` calls per second on average and
+    /// up to `burst` calls at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `rate` is positive and finite and `burst` is at least 1,
+    /// as a bucket could otherwise never let a call through.
+    pub fn new(rate: f64, burst: usize) -> TokenBucket {
+        assert!(rate > 0.0 && rate.is_finite(), "token bucket rate must be positive, not {}", rate);
+        assert!(burst > 0, "token bucket burst must be at least 1");
+        TokenBucket {
+            rate: rate,
+            burst: burst as f64,
+            tokens: Cell::new(burst as f64),
+            refilled_at: Cell::new(time::precise_time_ns()),
+        }
+    }
+
+    /// Takes a token if one is available, returning false otherwise.
+    pub fn try_acquire(&self) -> bool {
+        self.refill();
+        if self.tokens.get() >= 1.0 {
+            self.tokens.set(self.tokens.get() - 1.0);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Takes a token, sleeping until one is available.
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            // sleep a second at most at a time, keeping the cast in range
+            // however slow the rate
+            let wait = (1.0 - self.tokens.get()) / self.rate;
+            let wait = if wait > 1.0 { 1.0 } else { wait };
+            timer::sleep(Duration::microseconds((wait * 1e6).ceil() as i64));
+        }
+    }
+
+    fn refill(&self) {
+        let now = time::precise_time_ns();
+        let elapsed = (now - self.refilled_at.get()) as f64 / 1e9;
+        let tokens = self.tokens.get() + elapsed * self.rate;
+        self.tokens.set(if tokens > self.burst { self.burst } else { tokens });
+        self.refilled_at.set(now);
+    }
+}
+
+/// Throttles calls with a global token bucket and per-method buckets, so
+/// bulk jobs can be kept within what a server tolerates.
+pub struct RateLimiter {
+    global: Option<TokenBucket>,
+    methods: HashMap<string::String, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter which lets every call through.
+    pub fn new() -> RateLimiter {
+        RateLimiter { global: None, methods: HashMap::new() }
+    }
+
+    /// Limits all calls to `rate` per second, with bursts of up to `burst`.
+    /// Panics as `TokenBucket::new` does on an invalid rate or burst.
+    pub fn global(mut self, rate: f64, burst: usize) -> RateLimiter {
+        self.global = Some(TokenBucket::new(rate, burst));
+        self
+    }
+
+    /// Limits calls to `method` to `rate` per second, with bursts of up to
+    /// `burst`. These calls also count against the global limit.
+    pub fn method(mut self, method: &str, rate: f64, burst: usize) -> RateLimiter {
+        self.methods.insert(method.to_string(), TokenBucket::new(rate, burst));
+        self
+    }
+
+    /// Waits until a call to `method` is allowed by every applicable limit.
+    pub fn acquire(&self, method: &str) {
+        if let Some(bucket) = self.methods.get(method) {
+            bucket.acquire();
+        }
+        if let Some(ref bucket) = self.global {
+            bucket.acquire();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimiter, TokenBucket};
+
+    #[test]
+    fn test_burst_then_refill() {
+        let bucket = TokenBucket::new(1.0, 2);
+        assert!(bucket.try_acquire());
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+        // 1.5 seconds' worth of refill, capped at the burst size
+        bucket.tokens.set(0.0);
+        bucket.refilled_at.set(bucket.refilled_at.get() - 1_500_000_000);
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+        bucket.refilled_at.set(bucket.refilled_at.get() - 10_000_000_000);
+        bucket.refill();
+        assert_eq!(bucket.tokens.get(), 2.0);
+    }
+
+    #[test]
+    fn test_acquire_waits_for_a_token() {
+        let bucket = TokenBucket::new(1000.0, 1);
+        assert!(bucket.try_acquire());
+        bucket.acquire();
+        assert!(bucket.tokens.get() < 1.0);
+    }
+
+    #[test]
+    fn test_method_limits_count_globally() {
+        let limiter = RateLimiter::new().global(1.0, 2).method("slow", 1.0, 1);
+        limiter.acquire("slow");
+        assert!(!limiter.methods["slow".to_string()].try_acquire());
+        assert!(limiter.global.as_ref().unwrap().try_acquire());
+        assert!(!limiter.global.as_ref().unwrap().try_acquire());
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_zero_rate_is_refused() {
+        TokenBucket::new(0.0, 1);
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_zero_burst_is_refused() {
+        TokenBucket::new(1.0, 0);
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+/// Implements `Encodable` and `Decodable` for a C-like enum so that each
+/// variant travels as the given integer, as legacy APIs often encode a
+/// status as 0, 1, 2...
+///
+/// ```ignore
+/// xmlrpc_enum_codes!(Status { Active = 0, Suspended = 1, Deleted = 2 });
+/// ```
+#[macro_export]
+macro_rules! xmlrpc_enum_codes {
+    ($name:ident { $($variant:ident = $code:expr),+ }) => {
+        impl $crate::serialize::Encodable for $name {
+            fn encode<S: $crate::serialize::Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+                match *self {
+                    $($name::$variant => s.emit_i32($code)),+
+                }
+            }
+        }
+
+        impl $crate::serialize::Decodable for $name {
+            fn decode<D: $crate::serialize::Decoder>(d: &mut D) -> Result<$name, D::Error> {
+                let code = try!(d.read_i32());
+                $(if code == $code { return Ok($name::$variant); })+
+                Err(d.error(format!("unknown {} code {}", stringify!($name), code).as_slice()))
+            }
+        }
+    }
+}
+
+/// Implements `Encodable` and `Decodable` for a C-like enum so that each
+/// variant travels as the given string rather than its rust name.
+///
+/// ```ignore
+/// xmlrpc_enum_names!(Level { Low = "low", High = "HIGH" });
+/// ```
+#[macro_export]
+macro_rules! xmlrpc_enum_names {
+    ($name:ident { $($variant:ident = $string:expr),+ }) => {
+        impl $crate::serialize::Encodable for $name {
+            fn encode<S: $crate::serialize::Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+                match *self {
+                    $($name::$variant => s.emit_str($string)),+
+                }
+            }
+        }
+
+        impl $crate::serialize::Decodable for $name {
+            fn decode<D: $crate::serialize::Decoder>(d: &mut D) -> Result<$name, D::Error> {
+                let string = try!(d.read_str());
+                $(if string.as_slice() == $string { return Ok($name::$variant); })+
+                Err(d.error(format!("unknown {} name {}", stringify!($name), string).as_slice()))
+            }
+        }
+    }
+}
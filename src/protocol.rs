@@ -8,57 +8,767 @@
 
 // Rust XML-RPC library
 
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::default::Default;
 use std::string;
+use std::time::Duration;
+use std::i64;
 use rustc_serialize::{Encodable,Decodable};
+use time;
+use encoding::{Xml,Object,ParserError,DecoderError,EncoderError};
+use encoding::{parse_method_call,parse_method_response,try_encode,from_value,escape_str};
 
+/// The XML declaration written at the start of a request or response document
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum Declaration {
+    /// No declaration at all
+    Omit,
+    /// `<?xml version="1.0"?>`
+    Version,
+    /// `<?xml version="1.0" encoding="UTF-8"?>`
+    Encoding,
+    /// `<?xml version="1.0" encoding="UTF-8" standalone="yes"?>`
+    Standalone,
+}
+
+impl Declaration {
+    /// Returns the declaration as it is written into a document.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Declaration::Omit => "",
+            Declaration::Version => "<?xml version=\"1.0\"?>",
+            Declaration::Encoding => "<?xml version=\"1.0\" encoding=\"UTF-8\"?>",
+            Declaration::Standalone =>
+                "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>",
+        }
+    }
+}
+
+impl Default for Declaration {
+    fn default() -> Declaration { Declaration::Version }
+}
+
+/// Which method names a request accepts.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub enum MethodNames {
+    /// Only the characters the spec allows: letters, digits, `_`, `.`,
+    /// `:` and `/`
+    Strict,
+    /// Any name without whitespace or control characters
+    Lenient,
+    /// Any non-empty name; characters XML reserves are still escaped
+    Unchecked,
+}
+
+impl Default for MethodNames {
+    fn default() -> MethodNames { MethodNames::Strict }
+}
+
+impl MethodNames {
+    /// Returns true if `name` is acceptable under this policy.
+    pub fn allows(&self, name: &str) -> bool {
+        !name.is_empty() && match *self {
+            MethodNames::Strict => name.chars().all(|c| {
+                c.is_ascii() && (c.is_alphanumeric() || "_.:/".contains_char(c))
+            }),
+            MethodNames::Lenient => !name.chars().any(|c| c.is_whitespace() || c.is_control()),
+            MethodNames::Unchecked => true,
+        }
+    }
+}
+
+const PARAMS_START: &'static str = "<params>";
+const CALL_END: &'static str = "</params></methodCall>";
+
+#[derive(Clone, PartialEq, Show)]
 pub struct Request {
     pub method: string::String,
     pub body: string::String,
+    /// Sent as the `Idempotency-Key` header, letting the server recognise
+    /// retries of a call that is not naturally idempotent
+    pub idempotency_key: Option<string::String>,
+    /// Sent in the client's request ID header; one is generated if absent
+    pub request_id: Option<string::String>,
+    /// The instant, as given by `time::precise_time_ns`, after which the
+    /// caller no longer wants the result
+    pub deadline: Option<u64>,
+    /// HTTP headers to send with this call only, replacing any the client
+    /// would send under the same name
+    pub headers: Vec<(string::String, string::String)>,
+    // the first argument that failed to encode, reported by try_finalize
+    error: Option<EncoderError>,
+    omit_empty_params: bool,
 }
 
+#[derive(Clone, PartialEq, Show)]
 pub struct Response {
     pub body: string::String,
+    /// The HTTP headers the response arrived with, or is to be sent with,
+    /// in the order given
+    pub headers: Vec<(string::String, string::String)>,
+    // the body parsed once, on construction
+    params: Result<Vec<Xml>, ResponseError>,
+}
+
+/// Fault codes from the Specification for Fault Code Interoperability,
+/// http://xmlrpc-epi.sourceforge.net/specs/rfc.fault_codes.php
+pub mod fault_codes {
+    /// The document is not well-formed XML
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const UNSUPPORTED_ENCODING: i32 = -32701;
+    pub const INVALID_CHARACTER: i32 = -32702;
+    /// The document is XML but not a valid methodCall
+    pub const INVALID_XMLRPC: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+    pub const APPLICATION_ERROR: i32 = -32500;
+    pub const SYSTEM_ERROR: i32 = -32400;
+    pub const TRANSPORT_ERROR: i32 = -32300;
+}
+
+/// A failure in handling a call before any application code runs, as
+/// distinct from a fault the application raises. Each can be answered
+/// either with an HTTP error status or with a fault carrying the standard
+/// code, as the server operator prefers.
+#[derive(Clone, PartialEq, Show)]
+pub enum ServerError {
+    /// The body is not a well-formed methodCall
+    Malformed(ParserError),
+    /// The request's Content-Type is not text/xml
+    UnsupportedContentType(string::String),
+    /// The body exceeds the limit, in bytes
+    BodyTooLarge(usize),
+    MethodNotFound(string::String),
+    /// The parameters do not suit the method; carries a description
+    InvalidParams(string::String),
+}
+
+impl ServerError {
+    /// Returns the fault reporting the error, with its standard code.
+    pub fn to_fault(&self) -> Fault {
+        match *self {
+            ServerError::Malformed(ref e) => {
+                Fault::new(fault_codes::PARSE_ERROR, format!("parse error: {:?}", e).as_slice())
+            }
+            ServerError::UnsupportedContentType(ref ty) => {
+                Fault::new(fault_codes::TRANSPORT_ERROR,
+                           format!("unsupported content type {}", ty).as_slice())
+            }
+            ServerError::BodyTooLarge(limit) => {
+                Fault::new(fault_codes::TRANSPORT_ERROR,
+                           format!("request body exceeds {} bytes", limit).as_slice())
+            }
+            ServerError::MethodNotFound(ref method) => {
+                Fault::new(fault_codes::METHOD_NOT_FOUND,
+                           format!("method {} not found", method).as_slice())
+            }
+            ServerError::InvalidParams(ref detail) => {
+                Fault::new(fault_codes::INVALID_PARAMS,
+                           format!("invalid method parameters: {}", detail).as_slice())
+            }
+        }
+    }
+
+    /// Returns the HTTP status reporting the error, for servers that answer
+    /// transport failures outside XML-RPC. Errors about the call itself
+    /// are always answered with a fault, and return None.
+    pub fn http_status(&self) -> Option<u16> {
+        match *self {
+            ServerError::Malformed(_) => Some(400),
+            ServerError::UnsupportedContentType(_) => Some(415),
+            ServerError::BodyTooLarge(_) => Some(413),
+            ServerError::MethodNotFound(_) | ServerError::InvalidParams(_) => None,
+        }
+    }
+}
+
+/// A fault returned in place of a result.
+#[derive(Clone, PartialEq, Show)]
+pub struct Fault {
+    pub code: i32,
+    pub string: string::String,
+    /// Members beyond faultCode and faultString, such as a backtrace,
+    /// which some servers add
+    pub extra: Object,
+}
+
+impl Fault {
+    pub fn new(code: i32, string: &str) -> Fault {
+        Fault { code: code, string: string.to_string(), extra: BTreeMap::new() }
+    }
+
+    /// Adds a member to the fault struct beyond faultCode and faultString.
+    pub fn with_member(mut self, name: &str, value: Xml) -> Fault {
+        self.extra.insert(name.to_string(), value);
+        self
+    }
+
+    /// Reads a fault from the struct carried by a `<fault>` element.
+    pub fn from_xml(value: &Xml) -> Result<Fault, DecoderError> {
+        let mut members = match *value {
+            Xml::Object(ref members) => members.clone(),
+            ref value => {
+                return Err(DecoderError::ExpectedError("Object".to_string(), format!("{}", value)))
+            }
+        };
+        let code = match members.remove("faultCode") {
+            Some(Xml::I32(code)) => code,
+            Some(value) => {
+                return Err(DecoderError::ExpectedError("Number".to_string(), format!("{}", value)))
+            }
+            None => return Err(DecoderError::MissingFieldError("faultCode".to_string())),
+        };
+        let string = match members.remove("faultString") {
+            Some(Xml::String(string)) => string,
+            Some(value) => {
+                return Err(DecoderError::ExpectedError("String".to_string(), format!("{}", value)))
+            }
+            None => return Err(DecoderError::MissingFieldError("faultString".to_string())),
+        };
+        Ok(Fault { code: code, string: string, extra: members })
+    }
+
+    /// Returns the fault struct, including any extra members.
+    pub fn to_xml(&self) -> Xml {
+        let mut members = self.extra.clone();
+        members.insert("faultCode".to_string(), Xml::I32(self.code));
+        members.insert("faultString".to_string(), Xml::String(self.string.clone()));
+        Xml::Object(members)
+    }
+}
+
+/// The errors that can arise while extracting a result from a response.
+#[derive(Clone, PartialEq, Show)]
+pub enum ResponseError {
+    /// The body is not a well-formed `<methodResponse>`
+    ParseError(ParserError),
+    /// The server returned a fault
+    Fault(Fault),
+    /// The response has fewer parameters than the index asked for
+    MissingParam(usize),
+    /// The parameter, or the fault, does not have the expected type; the
+    /// error carries the path to the mismatch
+    DecodeError(DecoderError),
 }
 
 impl Request {
     pub fn new(method: &str) -> Request {
+        Request::new_with(method, Default::default())
+    }
+
+    /// Starts a request whose document begins with the given XML declaration.
+    /// A method name the spec does not allow is reported by `try_finalize`.
+    pub fn new_with(method: &str, declaration: Declaration) -> Request {
+        let mut name = string::String::new();
+        let _ = escape_str(&mut name, method, &Default::default());
+        let error = if MethodNames::Strict.allows(method) {
+            None
+        } else {
+            Some(EncoderError::InvalidMethodName)
+        };
         Request {
             method: method.to_string(),
             body: format!("\
-            <?xml version=\"1.0\"?>\
+            {}\
             <methodCall><methodName>{}</methodName>\
-                <params>", method),
+                <params>", declaration.as_str(), name),
+            idempotency_key: None,
+            request_id: None,
+            deadline: None,
+            headers: Vec::new(),
+            error: error,
+            omit_empty_params: false,
+        }
+    }
+
+    /// Starts a request, failing at once if `names` does not allow `method`.
+    pub fn try_new(method: &str, names: MethodNames) -> Result<Request, EncoderError> {
+        if !names.allows(method) {
+            return Err(EncoderError::InvalidMethodName);
         }
+        let mut request = Request::new(method);
+        request.error = None;
+        Ok(request)
+    }
+
+    /// Builds a finished request calling `method` with already parsed
+    /// parameter values. It takes the method name and parameters that
+    /// `parse_method_call` returns rather than a single `&Xml`: no `Xml`
+    /// value holds a whole `<methodCall>`, since the method name is not a
+    /// value.
+    pub fn from_xml(method: &str, params: &[Xml]) -> Request {
+        let mut request = Request::new(method);
+        for param in params.iter() {
+            request = request.argument(param);
+        }
+        request.finalize()
+    }
+
+    /// Parses a `<methodCall>` document, such as one received by a server or
+    /// proxy, into a finished request. The body is kept byte for byte, so
+    /// forwarding the request preserves the lexical form of every value
+    /// and any signature computed over it.
+    pub fn parse(body: &str) -> Result<Request, ParserError> {
+        let (method, _) = try!(parse_method_call(body, Default::default()));
+        let mut request = Request::new(method.as_slice());
+        request.body = body.to_string();
+        // the name is forwarded as received
+        request.error = None;
+        Ok(request)
+    }
+
+    /// Parses the parameters back out of a finished request.
+    pub fn params(&self) -> Result<Vec<Xml>, ParserError> {
+        let (_, params) = try!(parse_method_call(self.document().as_slice(), Default::default()));
+        Ok(params)
     }
 
+    /// Appends a parameter. A value that cannot be encoded is left out and
+    /// reported by `try_finalize` and `error`, and the client refuses to
+    /// send the request rather than send the later arguments out of place.
     pub fn argument<T: Encodable>(mut self, object: &T) -> Request {
-        let append_body = format!("<param>{}</param>", super::encode(object));
-        self.body = self.body + append_body.as_slice();
+        match try_encode(object) {
+            Ok(value) => self.push_param(value.as_slice()),
+            Err(e) => if self.error.is_none() { self.error = Some(e) },
+        }
+        self
+    }
+
+    /// Appends a parameter, failing if the value cannot be encoded.
+    pub fn try_argument<T: Encodable>(mut self, object: &T) -> Result<Request, EncoderError> {
+        let value = try!(try_encode(object));
+        self.push_param(value.as_slice());
+        Ok(self)
+    }
+
+    fn push_param(&mut self, value: &str) {
+        self.body.push_str(format!("<param><value>{}</value></param>", value).as_slice());
+    }
+
+    /// Attaches an idempotency key to the request.
+    pub fn idempotency_key(mut self, key: &str) -> Request {
+        self.idempotency_key = Some(key.to_string());
+        self
+    }
+
+    /// Sets the ID identifying this call across multiple hops.
+    pub fn request_id(mut self, id: &str) -> Request {
+        self.request_id = Some(id.to_string());
+        self
+    }
+
+    /// Adds an HTTP header to send with this call, such as a one-off
+    /// tracing flag or auth token. The client refuses to send the call if
+    /// the header is not a valid HTTP header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Request {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the deadline to `timeout` from now.
+    pub fn with_timeout(mut self, timeout: Duration) -> Request {
+        let timeout = timeout.num_nanoseconds().unwrap_or(i64::MAX);
+        let timeout = if timeout < 0 { 0 } else { timeout as u64 };
+        self.deadline = Some(time::precise_time_ns() + timeout);
         self
     }
 
+    /// Returns the time left before the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| {
+            let now = time::precise_time_ns();
+            let left = if deadline > now { deadline - now } else { 0 };
+            Duration::nanoseconds(left as i64)
+        })
+    }
+
+    /// Leaves `<params>` out entirely if the call has no parameters, rather
+    /// than sending an empty `<params></params>`.
+    pub fn omit_empty_params(mut self) -> Request {
+        self.omit_empty_params = true;
+        self
+    }
+
+    /// Closes the parameter list. Calling it more than once has no effect.
     pub fn finalize(mut self) -> Request {
-        self.body = self.body + "</params></methodCall>";
+        if !self.is_finalized() {
+            self.body = self.document();
+        }
         self
     }
 
+    /// Returns true once the parameter list has been closed.
+    pub fn is_finalized(&self) -> bool {
+        self.body.trim_right().ends_with("</methodCall>")
+    }
+
+    /// Returns the complete `<methodCall>` document, closing the parameter
+    /// list if `finalize` was never called.
+    pub fn document(&self) -> string::String {
+        if self.is_finalized() {
+            return self.body.clone();
+        }
+        if self.omit_empty_params && self.body.ends_with(PARAMS_START) {
+            let end = self.body.len() - PARAMS_START.len();
+            return format!("{}</methodCall>", &self.body[..end]);
+        }
+        format!("{}{}", self.body, CALL_END)
+    }
+
+    /// Finishes the request, failing if any argument could not be encoded.
+    pub fn try_finalize(self) -> Result<Request, EncoderError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.finalize()),
+        }
+    }
+
+    /// Returns the first error met while building the request, such as an
+    /// invalid method name. The client refuses to send a request with one.
+    pub fn error(&self) -> Option<EncoderError> {
+        self.error
+    }
+
 }
 
 impl Response {
     pub fn new(body: &str) -> Response {
         Response {
             body: body.to_string(),
+            headers: Vec::new(),
+            params: parse_params(body),
+        }
+    }
+
+    /// Adds an HTTP header to send with the response, such as Cache-Control
+    /// or a deprecation warning.
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|&&(ref n, _)| n.as_slice().eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_slice())
+    }
+
+    /// Builds a successful response carrying a single value, beginning with
+    /// the given XML declaration
+    pub fn success<T: Encodable>(object: &T, declaration: Declaration) -> Response {
+        Response::new(format!("{}\
+            <methodResponse>\
+            <params>\n<param>\n<value>{}</value>\n</param>\n</params>\
+            </methodResponse>", declaration.as_str(), super::encode(object)).as_slice())
+    }
+
+    /// Builds a fault response, beginning with the given XML declaration
+    pub fn failure(fault: &Fault, declaration: Declaration) -> Response {
+        Response::new(format!("{}\
+            <methodResponse>\
+            <fault>\n<value>{}</value>\n</fault>\
+            </methodResponse>", declaration.as_str(), fault.to_xml()).as_slice())
+    }
+
+    /// Returns true if the response carries a fault rather than parameters.
+    pub fn is_fault(&self) -> bool {
+        self.fault().is_some()
+    }
+
+    /// Returns the fault carried by the response, if any.
+    pub fn fault(&self) -> Option<Fault> {
+        match self.params {
+            Err(ResponseError::Fault(ref fault)) => Some(fault.clone()),
+            _ => None,
+        }
+    }
+
+    /// Decodes the parameter at `idx`.
+    pub fn result<T: Decodable>(&self, idx: usize) -> Result<T, ResponseError> {
+        let param = try!(self.value(idx));
+        from_value(param).map_err(ResponseError::DecodeError)
+    }
+
+    /// Returns the parameter at `idx` without decoding it, for inspection
+    /// or path queries.
+    pub fn value(&self, idx: usize) -> Result<&Xml, ResponseError> {
+        match self.params {
+            Ok(ref params) => params.get(idx).ok_or(ResponseError::MissingParam(idx)),
+            Err(ref e) => Err(e.clone()),
+        }
+    }
+
+    /// Returns every parameter of the response.
+    pub fn into_values(self) -> Result<Vec<Xml>, ResponseError> {
+        self.params
+    }
+}
+
+/// The parameters of a call or response, for decoding in parts.
+#[derive(Clone, Copy, PartialEq, Show)]
+pub struct Params<'a> {
+    values: &'a [Xml],
+}
+
+impl<'a> Params<'a> {
+    pub fn new(values: &'a [Xml]) -> Params<'a> {
+        Params { values: values }
+    }
+
+    /// Decodes leading parameters into `T`, usually a tuple such as
+    /// `(i32, String)`, returning it with the parameters left over. The
+    /// longest run of leading parameters that decodes is taken, so methods
+    /// with variadic trailing arguments can read the fixed ones first.
+    pub fn decode_prefix<T: Decodable>(&self) -> Result<(T, &'a [Xml]), DecoderError> {
+        let mut first_error = None;
+        for n in range(0, self.values.len() + 1).rev() {
+            match from_value(&Xml::Array(self.values[..n].to_vec())) {
+                Ok(value) => return Ok((value, &self.values[n..])),
+                Err(e) => if first_error.is_none() { first_error = Some(e) },
+            }
+        }
+        Err(first_error.unwrap())
+    }
+
+    /// Decodes the parameters into `T`, a tuple of `arity` elements, taking
+    /// missing trailing parameters as nil so that trailing `Option`
+    /// elements decode as None rather than failing for want of arguments.
+    /// More than `arity` parameters is an error.
+    pub fn decode_padded<T: Decodable>(&self, arity: usize) -> Result<T, DecoderError> {
+        if self.values.len() > arity {
+            return Err(DecoderError::ExpectedError(format!("at most {} parameters", arity),
+                                                   self.values.len().to_string()));
+        }
+        let mut values = self.values.to_vec();
+        while values.len() < arity {
+            values.push(Xml::Null);
         }
+        from_value(&Xml::Array(values))
     }
 
-    pub fn result<T: Decodable>(&self, idx: usize) -> Option<T> {
-        // FIXME: use idx
-        let resp = self.body.clone(); // FIXME: no need to clone
-        let val0 = "<params>\n<param>\n<value>"; // FIXME: use xml-rs rather than manual search
-        let idx0 = resp.find_str(val0).unwrap() + val0.len();
-        let val1 = "</value>\n</param>\n</params>";
-        let idx1 = resp.find_str(val1).unwrap();
-        let object: T = super::decode(resp.slice(idx0,idx1)).unwrap();
-        Some(object)
+    /// Decodes the parameters into `T`, a tuple of `arity` elements whose
+    /// last is a `Vec<Xml>`, as a variadic method takes them. The leading
+    /// elements are read as `decode_padded` reads them, and the last
+    /// collects every parameter after them, empty if there are none.
+    pub fn decode_variadic<T: Decodable>(&self, arity: usize) -> Result<T, DecoderError> {
+        if arity == 0 {
+            return Err(DecoderError::ExpectedError("a tuple ending in Vec<Xml>".to_string(),
+                                                   "arity 0".to_string()));
+        }
+        let fixed = cmp::min(arity - 1, self.values.len());
+        let mut values = self.values[..fixed].to_vec();
+        while values.len() < arity - 1 {
+            values.push(Xml::Null);
+        }
+        values.push(Xml::Array(self.values[fixed..].to_vec()));
+        from_value(&Xml::Array(values))
+    }
+}
+
+/// Reads a remaining deadline sent in milliseconds, as `Client::send_deadline`
+/// writes it, for servers to pass on to handlers or to fault early.
+pub fn deadline_from_header(value: &str) -> Option<Duration> {
+    value.trim().parse::<i64>().map(Duration::milliseconds)
+}
+
+fn parse_params(body: &str) -> Result<Vec<Xml>, ResponseError> {
+    match parse_method_response(body, Default::default()) {
+        Ok(Ok(params)) => Ok(params),
+        Ok(Err(value)) => match Fault::from_xml(&value) {
+            Ok(fault) => Err(ResponseError::Fault(fault)),
+            Err(e) => Err(ResponseError::DecodeError(e)),
+        },
+        Err(e) => Err(ResponseError::ParseError(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Declaration, Fault, Params, Request, Response, ResponseError};
+    use super::{ServerError, deadline_from_header, fault_codes};
+    use encoding::{DecoderError, Xml};
+    use fixtures;
+    use std::string;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_keeps_body() {
+        let body = "<?xml version=\"1.0\"?>\n<methodCall>\n  <methodName>math.scale</methodName>\n\
+                    <params><param><value><i4>007</i4></value></param>\
+                    <param><value><double>1.50</double></value></param></params>\n</methodCall>\n";
+        let request = Request::parse(body).unwrap();
+        assert_eq!(request.method.as_slice(), "math.scale");
+        assert!(request.is_finalized());
+        assert_eq!(request.document().as_slice(), body);
+        assert_eq!(request.params(), Ok(vec![Xml::I32(7), Xml::F64(1.5)]));
+        assert_eq!(request.error(), None);
+
+        assert!(Request::parse("<methodCall><methodName>x</methodName>").is_err());
+    }
+
+    #[test]
+    fn test_params_decode_prefix() {
+        let values = [Xml::I32(1), Xml::String("a".to_string()), Xml::Boolean(true), Xml::Null];
+        let params = Params::new(&values);
+        let ((n, s), rest): ((i32, string::String), _) = params.decode_prefix().unwrap();
+        assert_eq!((n, s.as_slice()), (1, "a"));
+        assert_eq!(rest, &values[2..]);
+        let result: Result<((string::String,), _), _> = params.decode_prefix();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_params_decode_padded() {
+        let values = [Xml::I32(1)];
+        let padded: (i32, Option<string::String>) = Params::new(&values).decode_padded(2).unwrap();
+        assert_eq!(padded, (1, None));
+        let missing: Result<(i32, i32), _> = Params::new(&values).decode_padded(2);
+        assert!(missing.is_err());
+        let values = [Xml::I32(1), Xml::I32(2), Xml::I32(3)];
+        let extra: Result<(i32, Option<i32>), _> = Params::new(&values).decode_padded(2);
+        assert!(extra.is_err());
+    }
+
+    #[test]
+    fn test_params_decode_variadic() {
+        let values = [Xml::I32(1), Xml::String("a".to_string()), Xml::Boolean(true)];
+        let (n, rest): (i32, Vec<Xml>) = Params::new(&values).decode_variadic(2).unwrap();
+        assert_eq!((n, rest), (1, values[1..].to_vec()));
+
+        let values = [Xml::I32(1)];
+        let (n, tag, rest): (i32, Option<string::String>, Vec<Xml>) =
+            Params::new(&values).decode_variadic(3).unwrap();
+        assert_eq!((n, tag, rest), (1, None, vec![]));
+        assert!(Params::new(&values).decode_variadic::<(i32,)>(0).is_err());
+    }
+
+    #[test]
+    fn test_declarations() {
+        let prologues = [
+            (Declaration::Omit, ""),
+            (Declaration::Version, "<?xml version=\"1.0\"?>"),
+            (Declaration::Encoding, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>"),
+            (Declaration::Standalone,
+             "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>"),
+        ];
+        for &(declaration, prologue) in prologues.iter() {
+            let call = Request::new_with("ping", declaration).document();
+            assert!(call.as_slice().starts_with(format!("{}<methodCall>", prologue).as_slice()));
+            let response = Response::success(&1i32, declaration);
+            assert!(response.body.as_slice()
+                        .starts_with(format!("{}<methodResponse>", prologue).as_slice()));
+            let failure = Response::failure(&Fault::new(1, "boom"), declaration);
+            assert!(failure.body.as_slice()
+                        .starts_with(format!("{}<methodResponse>", prologue).as_slice()));
+        }
+        assert!(Request::new("ping").document().as_slice().starts_with("<?xml version=\"1.0\"?>"));
+    }
+
+    #[test]
+    fn test_response_errors() {
+        match Response::new("<methodResponse><params>").result::<i32>(0) {
+            Err(ResponseError::ParseError(_)) => {}
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        let faulted = Response::new(fixtures::fault(4, "Too many parameters").as_slice());
+        assert_eq!(faulted.result::<i32>(0),
+                   Err(ResponseError::Fault(Fault::new(4, "Too many parameters"))));
+        let ok = Response::new(fixtures::response_ok(&7i32).as_slice());
+        assert_eq!(ok.result::<i32>(1), Err(ResponseError::MissingParam(1)));
+        match ok.result::<string::String>(0) {
+            Err(ResponseError::DecodeError(_)) => {}
+            other => panic!("expected a decode error, got {:?}", other),
+        }
+        // a fault struct without the required members cannot be read either
+        let bad_fault = "<methodResponse><fault><value><struct></struct></value></fault>\
+                         </methodResponse>";
+        assert_eq!(Response::new(bad_fault).result::<i32>(0),
+                   Err(ResponseError::DecodeError(
+                       DecoderError::MissingFieldError("faultCode".to_string()))));
+    }
+
+    #[test]
+    fn test_response_values() {
+        let values = [Xml::I32(1), Xml::String("two".to_string())];
+        let response = Response::new(fixtures::response_values(&values).as_slice());
+        assert_eq!(response.value(0), Ok(&values[0]));
+        assert_eq!(response.value(1), Ok(&values[1]));
+        assert_eq!(response.value(2), Err(ResponseError::MissingParam(2)));
+        assert_eq!(response.into_values(), Ok(values.to_vec()));
+
+        let faulted = Response::new(fixtures::fault(1, "boom").as_slice());
+        assert_eq!(faulted.value(0), Err(ResponseError::Fault(Fault::new(1, "boom"))));
+        assert_eq!(faulted.into_values(), Err(ResponseError::Fault(Fault::new(1, "boom"))));
+    }
+
+    #[test]
+    fn test_fault_extra_members() {
+        let fault = Fault::new(500, "boom")
+            .with_member("backtrace", Xml::Array(vec![Xml::String("main".to_string())]))
+            .with_member("retryable", Xml::Boolean(false));
+        let response = Response::new(fixtures::fault_with(&fault).as_slice());
+        assert_eq!(response.fault(), Some(fault.clone()));
+        let failure = Response::failure(&fault, Declaration::Version);
+        assert_eq!(failure.fault(), Some(fault.clone()));
+        assert_eq!(Fault::from_xml(&fault.to_xml()), Ok(fault));
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let response = Response::success(&1i32, Declaration::Version)
+            .with_header("Cache-Control", "no-store")
+            .with_header("Warning", "299 - \"deprecated\"")
+            .with_header("cache-control", "max-age=60");
+        assert_eq!(response.header("cache-control"), Some("no-store"));
+        assert_eq!(response.header("WARNING"), Some("299 - \"deprecated\""));
+        assert_eq!(response.header("Expires"), None);
+        assert_eq!(response.headers.len(), 3);
+        assert_eq!(response.result::<i32>(0), Ok(1));
+    }
+
+    #[test]
+    fn test_deadline_round_trip() {
+        assert_eq!(Request::new("ping").remaining(), None);
+        let request = Request::new("ping").with_timeout(Duration::seconds(30));
+        let remaining = request.remaining().unwrap();
+        assert!(remaining <= Duration::seconds(30) && remaining > Duration::seconds(29));
+
+        // as the client sends it and a server reads it back
+        let header = remaining.num_milliseconds().to_string();
+        assert_eq!(deadline_from_header(header.as_slice()),
+                   Some(Duration::milliseconds(remaining.num_milliseconds())));
+        assert_eq!(deadline_from_header(" 1500 "), Some(Duration::milliseconds(1500)));
+        assert_eq!(deadline_from_header("soon"), None);
+
+        let expired = Request::new("ping").with_timeout(Duration::zero());
+        assert_eq!(expired.remaining(), Some(Duration::zero()));
+    }
+
+    #[test]
+    fn test_server_errors() {
+        let malformed = Request::parse("<methodCall>").unwrap_err();
+        let errors = [
+            (ServerError::Malformed(malformed), fault_codes::PARSE_ERROR, Some(400)),
+            (ServerError::UnsupportedContentType("text/html".to_string()),
+             fault_codes::TRANSPORT_ERROR, Some(415)),
+            (ServerError::BodyTooLarge(1024), fault_codes::TRANSPORT_ERROR, Some(413)),
+            (ServerError::MethodNotFound("math.div".to_string()),
+             fault_codes::METHOD_NOT_FOUND, None),
+            (ServerError::InvalidParams("expected 2".to_string()),
+             fault_codes::INVALID_PARAMS, None),
+        ];
+        for &(ref error, code, status) in errors.iter() {
+            let fault = error.to_fault();
+            assert_eq!(fault.code, code);
+            assert_eq!(error.http_status(), status);
+            let response = Response::failure(&fault, Declaration::Version);
+            assert_eq!(response.fault(), Some(fault));
+        }
+        assert_eq!(ServerError::MethodNotFound("math.div".to_string()).to_fault().string.as_slice(),
+                   "method math.div not found");
     }
 }
@@ -0,0 +1,171 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::string;
+
+use client::Client;
+use encoding::{Xml, ParserError};
+use protocol::{Request, Response};
+
+/// The errors that can arise while forwarding a call.
+#[derive(Clone, PartialEq, Show)]
+pub enum ProxyError {
+    /// The incoming call could not be parsed
+    ParseError(ParserError),
+    /// The upstream server did not produce a response
+    UpstreamError,
+}
+
+/// Forwards calls to an upstream server, optionally rewriting method names
+/// and parameters on the way. Responses, including faults, are relayed
+/// unchanged.
+pub struct Proxy {
+    client: Client,
+    method_hook: Option<Box<Fn(&str) -> string::String + 'static>>,
+    params_hook: Option<Box<Fn(&str, Vec<Xml>) -> Vec<Xml> + 'static>>,
+}
+
+impl Proxy {
+    /// Creates a proxy forwarding calls through `client`.
+    pub fn new(client: Client) -> Proxy {
+        Proxy { client: client, method_hook: None, params_hook: None }
+    }
+
+    /// Rewrites the method name of each forwarded call.
+    pub fn rewrite_method<F>(mut self, hook: F) -> Proxy where
+        F: Fn(&str) -> string::String + 'static,
+    {
+        self.method_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Rewrites the parameters of each forwarded call. The hook receives the
+    /// original method name.
+    pub fn rewrite_params<F>(mut self, hook: F) -> Proxy where
+        F: Fn(&str, Vec<Xml>) -> Vec<Xml> + 'static,
+    {
+        self.params_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Forwards a parsed call upstream and returns the upstream response.
+    pub fn forward(&self, request: &Request) -> Result<Response, ProxyError> {
+        if self.method_hook.is_none() && self.params_hook.is_none() {
+            return self.send(request);
+        }
+        let params = match request.params() {
+            Ok(params) => params,
+            Err(e) => return Err(ProxyError::ParseError(e)),
+        };
+        let method = match self.method_hook {
+            Some(ref hook) => (**hook)(request.method.as_slice()),
+            None => request.method.clone(),
+        };
+        let params = match self.params_hook {
+            Some(ref hook) => (**hook)(request.method.as_slice(), params),
+            None => params,
+        };
+        // the call's metadata travels with it, as it does when unchanged
+        let mut rewritten = Request::from_xml(method.as_slice(), params.as_slice());
+        rewritten.idempotency_key = request.idempotency_key.clone();
+        rewritten.request_id = request.request_id.clone();
+        rewritten.deadline = request.deadline;
+        rewritten.headers = request.headers.clone();
+        self.send(&rewritten)
+    }
+
+    /// Parses an incoming `<methodCall>` document and forwards it upstream.
+    pub fn forward_str(&self, body: &str) -> Result<Response, ProxyError> {
+        match Request::parse(body) {
+            Ok(request) => self.forward(&request),
+            Err(e) => Err(ProxyError::ParseError(e)),
+        }
+    }
+
+    fn send(&self, request: &Request) -> Result<Response, ProxyError> {
+        match self.client.remote_call(request) {
+            Some(response) => Ok(response),
+            None => Err(ProxyError::UpstreamError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Proxy;
+    use client::Client;
+    use encoding::Xml;
+    use fixtures;
+    use protocol::Request;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::string;
+    use std::time::Duration;
+    use transport::{MockStream, http_response};
+
+    fn proxy<F>(response: &str, client: F) -> (Proxy, Rc<RefCell<Vec<u8>>>) where
+        F: FnOnce(Client) -> Client,
+    {
+        let (stream, written) = MockStream::new(http_response(200, response).as_slice());
+        let upstream = Client::from_stream(stream, "http://upstream/RPC2").unwrap();
+        (Proxy::new(client(upstream)), written)
+    }
+
+    // splits what the upstream received into its head and its body
+    fn received(written: &Rc<RefCell<Vec<u8>>>) -> (string::String, string::String) {
+        let sent = string::String::from_utf8(written.borrow().clone()).unwrap();
+        let idx = sent.as_slice().find_str("\r\n\r\n").unwrap();
+        (sent[..idx + 2].to_string(), sent[idx + 4..].to_string())
+    }
+
+    #[test]
+    fn test_forward_unchanged() {
+        let (proxy, written) = proxy(fixtures::response_ok(&3i32).as_slice(), |c| c);
+        let body = "<methodCall><methodName>math.add</methodName><params>\
+                    <param><value><i4>1</i4></value></param></params></methodCall>";
+        let response = proxy.forward_str(body).unwrap();
+        assert_eq!(response.result::<i32>(0), Ok(3));
+        assert_eq!(received(&written).1.as_slice(), body);
+    }
+
+    #[test]
+    fn test_forward_rewritten() {
+        let (proxy, written) = proxy(fixtures::fault(4, "no").as_slice(), |c| c);
+        let proxy = proxy
+            .rewrite_method(|method| format!("v2.{}", method))
+            .rewrite_params(|method, mut params| {
+                assert_eq!(method, "math.add");
+                params.push(Xml::I32(2));
+                params
+            });
+        let request = Request::from_xml("math.add", &[Xml::I32(1)]);
+        let response = proxy.forward(&request).unwrap();
+        assert_eq!(response.fault().unwrap().code, 4);
+        let forwarded = Request::parse(received(&written).1.as_slice()).unwrap();
+        assert_eq!(forwarded.method.as_slice(), "v2.math.add");
+        assert_eq!(forwarded.params(), Ok(vec![Xml::I32(1), Xml::I32(2)]));
+    }
+
+    #[test]
+    fn test_rewrite_keeps_metadata() {
+        let (proxy, written) = proxy(fixtures::response_ok(&true).as_slice(), |c| {
+            c.with_request_id("X-Request-Id").send_deadline("X-Deadline")
+        });
+        let proxy = proxy.rewrite_method(|method| method.to_string());
+        let request = Request::new("ping").request_id("abc").idempotency_key("k1")
+            .with_header("X-Trace", "1").with_timeout(Duration::seconds(60)).finalize();
+        assert!(proxy.forward(&request).is_ok());
+        let head = received(&written).0;
+        assert!(head.as_slice().contains("\r\nX-Request-Id: abc\r\n"));
+        assert!(head.as_slice().contains("\r\nIdempotency-Key: k1\r\n"));
+        assert!(head.as_slice().contains("\r\nX-Trace: 1\r\n"));
+        assert!(head.as_slice().contains("\r\nX-Deadline: "));
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::string;
+
+use encoding::Xml;
+
+/// The expected type of a parameter or of a value nested within one.
+#[derive(Clone, PartialEq, Show)]
+pub enum Type {
+    Int,
+    Double,
+    Boolean,
+    String,
+    DateTime,
+    Base64,
+    Nil,
+    /// Any value at all
+    Any,
+    /// An array whose elements all have the given type
+    Array(Box<Type>),
+    /// A struct with at least the given members; others are allowed
+    Struct(Vec<(string::String, Type)>),
+    /// The given type, or nil
+    Nullable(Box<Type>),
+}
+
+/// The errors that can arise while validating parameters.
+#[derive(Clone, PartialEq, Show)]
+pub enum SchemaError {
+    /// The number of parameters expected and the number found
+    ParamCount(usize, usize),
+    /// The path to the offending value, e.g. `params[1].nodes[0]`, and the
+    /// type expected there
+    Mismatch(string::String, Type),
+    /// The path to a struct and the name of the member it lacks
+    MissingMember(string::String, string::String),
+}
+
+/// The parameter types of a method, against which calls can be checked
+/// before they are sent or dispatched.
+#[derive(Clone, PartialEq, Show)]
+pub struct Signature {
+    returns: Type,
+    params: Vec<Type>,
+}
+
+impl Signature {
+    /// Creates a signature taking no parameters and returning any value.
+    pub fn new() -> Signature {
+        Signature { returns: Type::Any, params: Vec::new() }
+    }
+
+    /// Sets the type of the value returned.
+    pub fn returns(mut self, ty: Type) -> Signature {
+        self.returns = ty;
+        self
+    }
+
+    /// Returns the type of the value returned.
+    pub fn return_type(&self) -> &Type {
+        &self.returns
+    }
+
+    /// Appends a parameter of type `ty`.
+    pub fn param(mut self, ty: Type) -> Signature {
+        self.params.push(ty);
+        self
+    }
+
+    /// Returns the parameter types.
+    pub fn params(&self) -> &[Type] {
+        self.params.as_slice()
+    }
+
+    /// Returns the signature as system.methodSignature describes it: an
+    /// array of type names, the return type first.
+    pub fn to_xml(&self) -> Xml {
+        let names = Some(&self.returns).into_iter().chain(self.params.iter());
+        Xml::Array(names.map(|ty| Xml::String(ty.name().to_string())).collect())
+    }
+
+    /// Checks that `params` has the number and types of parameters the
+    /// signature describes.
+    pub fn validate(&self, params: &[Xml]) -> Result<(), SchemaError> {
+        if params.len() != self.params.len() {
+            return Err(SchemaError::ParamCount(self.params.len(), params.len()));
+        }
+        for (i, (ty, value)) in self.params.iter().zip(params.iter()).enumerate() {
+            try!(check(ty, value, format!("params[{}]", i)));
+        }
+        Ok(())
+    }
+}
+
+impl Type {
+    /// Returns the name introspection gives the type, "undef" for `Any`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Type::Int => "int",
+            Type::Double => "double",
+            Type::Boolean => "boolean",
+            Type::String => "string",
+            Type::DateTime => "dateTime.iso8601",
+            Type::Base64 => "base64",
+            Type::Nil => "nil",
+            Type::Any => "undef",
+            Type::Array(_) => "array",
+            Type::Struct(_) => "struct",
+            Type::Nullable(ref inner) => inner.name(),
+        }
+    }
+}
+
+fn check(ty: &Type, value: &Xml, path: string::String) -> Result<(), SchemaError> {
+    let matches = match (ty, value) {
+        (&Type::Any, _) => true,
+        (&Type::Int, &Xml::I32(_)) => true,
+        (&Type::Double, &Xml::F64(_)) => true,
+        (&Type::Boolean, &Xml::Boolean(_)) => true,
+        (&Type::String, &Xml::String(_)) => true,
+        (&Type::DateTime, &Xml::DateTime(_)) => true,
+        (&Type::Base64, &Xml::Base64(_)) => true,
+        (&Type::Nil, &Xml::Null) => true,
+        (&Type::Nullable(_), &Xml::Null) => true,
+        (&Type::Nullable(ref inner), value) => return check(&**inner, value, path),
+        (&Type::Array(ref elt), &Xml::Array(ref array)) => {
+            for (i, value) in array.iter().enumerate() {
+                try!(check(&**elt, value, format!("{}[{}]", path, i)));
+            }
+            true
+        }
+        (&Type::Struct(ref members), &Xml::Object(ref map)) => {
+            for &(ref name, ref ty) in members.iter() {
+                match map.get(name) {
+                    Some(value) => try!(check(ty, value, format!("{}.{}", path, name))),
+                    None => return Err(SchemaError::MissingMember(path, name.clone())),
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+    if matches { Ok(()) } else { Err(SchemaError::Mismatch(path, ty.clone())) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SchemaError, Signature, Type};
+    use encoding::Xml;
+    use std::collections::BTreeMap;
+
+    fn lookup_node() -> Signature {
+        let node = Type::Struct(vec![("name".to_string(), Type::String),
+                                     ("pid".to_string(), Type::Nullable(Box::new(Type::Int)))]);
+        Signature::new().returns(Type::Array(Box::new(Type::String)))
+            .param(Type::String)
+            .param(Type::Array(Box::new(node)))
+    }
+
+    fn node(name: &str, pid: Xml) -> Xml {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_string(), Xml::String(name.to_string()));
+        map.insert("pid".to_string(), pid);
+        Xml::Object(map)
+    }
+
+    #[test]
+    fn test_valid_params() {
+        let params = [Xml::String("/caller".to_string()),
+                      Xml::Array(vec![node("a", Xml::I32(1)), node("b", Xml::Null)])];
+        assert_eq!(lookup_node().validate(&params), Ok(()));
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let signature = lookup_node();
+        assert_eq!(signature.validate(&[Xml::String("/caller".to_string())]),
+                   Err(SchemaError::ParamCount(2, 1)));
+
+        let params = [Xml::I32(1), Xml::Array(vec![])];
+        assert_eq!(signature.validate(&params),
+                   Err(SchemaError::Mismatch("params[0]".to_string(), Type::String)));
+
+        let params = [Xml::String("/caller".to_string()),
+                      Xml::Array(vec![node("a", Xml::I32(1)), node("b", Xml::Boolean(true))])];
+        assert_eq!(signature.validate(&params),
+                   Err(SchemaError::Mismatch("params[1][1].pid".to_string(), Type::Int)));
+
+        let params = [Xml::String("/caller".to_string()),
+                      Xml::Array(vec![Xml::Object(BTreeMap::new())])];
+        assert_eq!(signature.validate(&params),
+                   Err(SchemaError::MissingMember("params[1][0]".to_string(), "name".to_string())));
+    }
+
+    fn names(v: &[&str]) -> Xml {
+        Xml::Array(v.iter().map(|s| Xml::String(s.to_string())).collect())
+    }
+
+    #[test]
+    fn test_signature_to_xml() {
+        assert_eq!(lookup_node().to_xml(), names(&["array", "string", "array"]));
+        assert_eq!(Signature::new().param(Type::Nullable(Box::new(Type::Int))).to_xml(),
+                   names(&["undef", "int"]));
+    }
+}
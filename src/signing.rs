@@ -0,0 +1,164 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+//! HMAC-SHA256 signatures of request bodies, for servers which
+//! authenticate calls with a shared secret sent alongside the document.
+
+use std::ascii::AsciiExt;
+use std::num::Int;
+use std::string;
+
+use rustc_serialize::hex::ToHex;
+
+const BLOCK_LEN: usize = 64;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Returns the signature of `body` under `key`: its HMAC-SHA256 in
+/// lowercase hex.
+pub fn sign(key: &[u8], body: &str) -> string::String {
+    hmac_sha256(key, body.as_bytes()).as_slice().to_hex()
+}
+
+/// Checks that `signature` is the signature of `body` under `key`. Hex
+/// digits are accepted in either case, and the comparison takes the same
+/// time wherever the signatures differ.
+pub fn verify(key: &[u8], body: &str, signature: &str) -> bool {
+    let expected = sign(key, body);
+    let signature = signature.trim().to_ascii_lowercase();
+    if signature.len() != expected.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (a, b) in expected.as_bytes().iter().zip(signature.as_bytes().iter()) {
+        diff |= *a ^ *b;
+    }
+    diff == 0
+}
+
+/// Computes the HMAC-SHA256 of `message` under `key` (RFC 2104).
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        let digest = sha256(key);
+        for (b, k) in block.iter_mut().zip(digest.iter()) {
+            *b = *k;
+        }
+    } else {
+        for (b, k) in block.iter_mut().zip(key.iter()) {
+            *b = *k;
+        }
+    }
+
+    let mut inner: Vec<u8> = block.iter().map(|b| *b ^ 0x36).collect();
+    inner.push_all(message);
+    let mut outer: Vec<u8> = block.iter().map(|b| *b ^ 0x5c).collect();
+    outer.push_all(&sha256(inner.as_slice()));
+    sha256(outer.as_slice())
+}
+
+/// Computes the SHA-256 digest of `data` (FIPS 180-4).
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+        0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % BLOCK_LEN != 56 {
+        padded.push(0);
+    }
+    let bits = (data.len() as u64) * 8;
+    for i in range(0, 8) {
+        padded.push((bits >> (56 - 8 * i)) as u8);
+    }
+
+    for chunk in padded.chunks(BLOCK_LEN) {
+        let mut w = [0u32; 64];
+        for i in range(0, 16) {
+            w[i] = (chunk[4 * i] as u32) << 24 | (chunk[4 * i + 1] as u32) << 16
+                 | (chunk[4 * i + 2] as u32) << 8 | chunk[4 * i + 3] as u32;
+        }
+        for i in range(16, 64) {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+        }
+
+        let (mut a, mut b, mut c, mut d) = (h[0], h[1], h[2], h[3]);
+        let (mut e, mut f, mut g, mut hh) = (h[4], h[5], h[6], h[7]);
+        for i in range(0, 64) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh + s1 + ch + K[i] + w[i];
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0 + maj;
+            hh = g;
+            g = f;
+            f = e;
+            e = d + t1;
+            d = c;
+            c = b;
+            b = a;
+            a = t1 + t2;
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh].iter()) {
+            *x = *x + *y;
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (i, word) in h.iter().enumerate() {
+        for j in range(0, 4) {
+            digest[4 * i + j] = (*word >> (24 - 8 * j)) as u8;
+        }
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hmac_sha256, sign, verify};
+    use std::ascii::AsciiExt;
+    use rustc_serialize::hex::ToHex;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test cases 2 and 6
+        assert_eq!(hmac_sha256(b"Jefe", b"what do ya want for nothing?").as_slice().to_hex().as_slice(),
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        let key = [0xaau8; 131];
+        assert_eq!(hmac_sha256(&key, b"Test Using Larger Than Block-Size Key - Hash Key First")
+                       .as_slice().to_hex().as_slice(),
+                   "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+    }
+
+    #[test]
+    fn test_verify() {
+        let body = "<methodCall><methodName>ping</methodName></methodCall>";
+        let signature = sign(b"secret", body);
+        assert!(verify(b"secret", body, signature.as_slice()));
+        assert!(verify(b"secret", body, signature.as_slice().to_ascii_uppercase().as_slice()));
+        assert!(!verify(b"other", body, signature.as_slice()));
+        assert!(!verify(b"secret", "<methodCall/>", signature.as_slice()));
+        assert!(!verify(b"secret", body, &signature[..10]));
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2014-2015 Galen Clark Haynes
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Rust XML-RPC library
+
+use std::ascii::AsciiExt;
+use std::cell::RefCell;
+use std::io;
+use std::io::{BufferedStream, IoError, IoResult, Stream};
+use std::num;
+use std::rc::Rc;
+use std::string;
+
+/// An HTTP response, read in full from a connection.
+#[derive(Clone, PartialEq, Show)]
+pub struct HttpResponse {
+    /// The status code, 200 for any XML-RPC response
+    pub status: u16,
+    /// The headers, in the order received
+    pub headers: Vec<(string::String, string::String)>,
+    pub body: string::String,
+}
+
+/// A connection able to carry HTTP POSTs of XML-RPC documents.
+pub trait Connection {
+    /// Posts `body` to `path` on `host` with additional `headers`, returning
+    /// the response whatever its status.
+    fn post(&mut self, path: &str, host: &str,
+            headers: &[(string::String, string::String)],
+            body: &str) -> IoResult<HttpResponse>;
+}
+
+impl<S: Stream> Connection for BufferedStream<S> {
+    fn post(&mut self, path: &str, host: &str,
+            headers: &[(string::String, string::String)],
+            body: &str) -> IoResult<HttpResponse> {
+        try!(write!(self, "POST {} HTTP/1.1\r\n", path));
+        try!(write!(self, "Host: {}\r\n", host));
+        try!(write!(self, "Content-Type: text/xml\r\n"));
+        try!(write!(self, "Content-Length: {}\r\n", body.len()));
+        for &(ref name, ref value) in headers.iter() {
+            try!(write!(self, "{}: {}\r\n", name, value));
+        }
+        try!(write!(self, "\r\n"));
+        try!(self.write_str(body));
+        try!(self.flush());
+        read_response(self)
+    }
+}
+
+/// Reads an HTTP response from `reader`, consuming it entirely so that the
+/// connection can carry another request. The body is delimited by chunked
+/// transfer coding or Content-Length when either is present, otherwise by
+/// end of stream.
+pub fn read_response<B: Buffer>(reader: &mut B) -> IoResult<HttpResponse> {
+    let status_line = try!(reader.read_line());
+    let status = match status_line.as_slice().split(' ').nth(1).and_then(|code| code.parse()) {
+        Some(status) => status,
+        None => return Err(IoError {
+            kind: io::InvalidInput,
+            desc: "malformed HTTP status line",
+            detail: Some(status_line.as_slice().trim().to_string()),
+        }),
+    };
+
+    let mut headers = Vec::new();
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let line = try!(reader.read_line());
+        let line = line.as_slice().trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(idx) = line.find(':') {
+            let (name, value) = (line[..idx].trim(), line[idx + 1..].trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>();
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.split(',').any(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+            }
+            headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    // chunked coding takes precedence over Content-Length (RFC 7230, 3.3.3)
+    let bytes = match (chunked, content_length) {
+        (true, _) => try!(read_chunked(reader)),
+        (false, Some(len)) => try!(reader.read_exact(len)),
+        (false, None) => try!(reader.read_to_end()),
+    };
+    match string::String::from_utf8(bytes) {
+        Ok(body) => Ok(HttpResponse { status: status, headers: headers, body: body }),
+        Err(_) => Err(io::standard_error(io::InvalidInput)),
+    }
+}
+
+/// Reads a body in chunked transfer coding, discarding any trailer.
+fn read_chunked<B: Buffer>(reader: &mut B) -> IoResult<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line = try!(reader.read_line());
+        // the size may be followed by chunk extensions, which are ignored
+        let size = line.as_slice().split(';').next().unwrap().trim();
+        let size = match num::from_str_radix::<usize>(size, 16) {
+            Some(size) => size,
+            None => return Err(IoError {
+                kind: io::InvalidInput,
+                desc: "malformed chunk size",
+                detail: Some(line.as_slice().trim().to_string()),
+            }),
+        };
+        if size == 0 {
+            break;
+        }
+        body.push_all(try!(reader.read_exact(size)).as_slice());
+        try!(reader.read_line()); // the CRLF ending the chunk
+    }
+    loop {
+        let line = try!(reader.read_line());
+        if line.as_slice().trim().is_empty() {
+            return Ok(body);
+        }
+    }
+}
+
+/// A stream which replays `response` and records everything written to
+/// it, standing in for a server in tests.
+#[cfg(test)]
+pub struct MockStream {
+    response: io::MemReader,
+    written: Rc<RefCell<Vec<u8>>>,
+}
+
+#[cfg(test)]
+impl MockStream {
+    /// Returns the stream and a handle on the bytes written to it.
+    pub fn new(response: &str) -> (MockStream, Rc<RefCell<Vec<u8>>>) {
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let stream = MockStream {
+            response: io::MemReader::new(response.as_bytes().to_vec()),
+            written: written.clone(),
+        };
+        (stream, written)
+    }
+}
+
+#[cfg(test)]
+impl Reader for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.response.read(buf)
+    }
+}
+
+#[cfg(test)]
+impl Writer for MockStream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        self.written.borrow_mut().push_all(buf);
+        Ok(())
+    }
+}
+
+/// Returns an HTTP response carrying `body` with the given status.
+#[cfg(test)]
+pub fn http_response(status: u16, body: &str) -> string::String {
+    format!("HTTP/1.1 {} -\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+            status, body.len(), body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_response;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_read_content_length() {
+        let mut reader = BufReader::new(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhelloHTTP/1.1");
+        let response = read_response(&mut reader).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body.as_slice(), "hello");
+        assert_eq!(reader.read_to_end().unwrap().as_slice(), b"HTTP/1.1");
+    }
+
+    #[test]
+    fn test_read_chunked() {
+        let mut reader = BufReader::new(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\nnext");
+        let response = read_response(&mut reader).unwrap();
+        assert_eq!(response.body.as_slice(), "hello world");
+        assert_eq!(reader.read_to_end().unwrap().as_slice(), b"next");
+    }
+
+    #[test]
+    fn test_read_error_status_drains_body() {
+        let mut reader = BufReader::new(b"HTTP/1.1 500 Internal Server Error\r\n\
+            Content-Length: 4\r\n\r\nboomnext");
+        let response = read_response(&mut reader).unwrap();
+        assert_eq!(response.status, 500);
+        assert_eq!(response.body.as_slice(), "boom");
+        assert_eq!(reader.read_to_end().unwrap().as_slice(), b"next");
+    }
+}